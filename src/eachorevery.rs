@@ -1,18 +1,200 @@
 use core::panic;
 use hashbrown::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::ops::{Deref, Range};
 use std::sync::Arc;
 
 use super::eoefilter::{EachOrEveryFilterBuilder, EachOrEveryFilter};
 
 #[cfg_attr(debug_assertions,derive(Debug))]
-#[derive(Clone,Hash)]
+#[derive(Clone)]
 pub(super) enum EachOrEveryIndex {
     Unindexed,
-    Indexed(Arc<Vec<usize>>),
+    /* (start, len): as Unindexed but only a window of the data */
+    Window(usize,usize),
+    Indexed(SharedIndex),
+    /* (data index, end position) for each run. Ends are exclusive and strictly increasing */
+    RunLength(Arc<Vec<(usize,usize)>>),
+    /* As Every but with a known length */
+    Repeat(usize),
     Every
 }
 
+impl EachOrEveryIndex {
+    /* The index to use for new data built from window_data() */
+    fn unwindowed(&self) -> EachOrEveryIndex {
+        match self {
+            EachOrEveryIndex::Window(_,_) => EachOrEveryIndex::Unindexed,
+            x => x.clone()
+        }
+    }
+
+    /* Cheap test for an identical (not merely equal) index */
+    pub(super) fn same_as(&self, other: &EachOrEveryIndex) -> bool {
+        match (self,other) {
+            (EachOrEveryIndex::Unindexed,EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::Every,EachOrEveryIndex::Every) => true,
+            (EachOrEveryIndex::Window(a_start,a_len),EachOrEveryIndex::Window(b_start,b_len)) => a_start == b_start && a_len == b_len,
+            (EachOrEveryIndex::Indexed(a),EachOrEveryIndex::Indexed(b)) => a.same_as(b),
+            (EachOrEveryIndex::RunLength(a),EachOrEveryIndex::RunLength(b)) => Arc::ptr_eq(a,b),
+            (EachOrEveryIndex::Repeat(a),EachOrEveryIndex::Repeat(b)) => a == b,
+            _ => false
+        }
+    }
+
+    pub(super) fn is_constant(&self) -> bool {
+        matches!(self,EachOrEveryIndex::Every | EachOrEveryIndex::Repeat(_))
+    }
+}
+
+/* An index which may be a part of a larger one, so that slices can share it */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone)]
+pub(super) struct SharedIndex {
+    index: Arc<Vec<usize>>,
+    start: usize,
+    len: usize
+}
+
+impl SharedIndex {
+    pub(super) fn new(index: Vec<usize>) -> SharedIndex {
+        SharedIndex::from_arc(Arc::new(index))
+    }
+
+    pub(super) fn from_arc(index: Arc<Vec<usize>>) -> SharedIndex {
+        SharedIndex { start: 0, len: index.len(), index }
+    }
+
+    fn same_as(&self, other: &SharedIndex) -> bool {
+        Arc::ptr_eq(&self.index,&other.index) && self.start == other.start && self.len == other.len
+    }
+
+    fn slice(&self, range: Range<usize>) -> SharedIndex {
+        SharedIndex {
+            index: self.index.clone(),
+            start: self.start + range.start,
+            len: range.end - range.start
+        }
+    }
+}
+
+impl Deref for SharedIndex {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        &self.index[self.start..(self.start+self.len)]
+    }
+}
+
+/* A value per data item, computed lazily. Dense when the data is no bigger than the number
+ * of index entries which will look it up, otherwise sparse, so that the cost stays in
+ * proportion to the index even for a small view of a large dictionary.
+ */
+pub(super) enum DataCache<Y> {
+    Dense(Vec<Option<Y>>),
+    Sparse(HashMap<usize,Y>)
+}
+
+impl<Y> DataCache<Y> {
+    pub(super) fn new(data_len: usize, lookups: usize) -> DataCache<Y> {
+        if data_len <= lookups {
+            DataCache::Dense((0..data_len).map(|_| None).collect())
+        } else {
+            DataCache::Sparse(HashMap::new())
+        }
+    }
+
+    pub(super) fn get_or_insert_with<F>(&mut self, index: usize, f: F) -> &mut Y where F: FnOnce() -> Y {
+        match self {
+            DataCache::Dense(values) => values[index].get_or_insert_with(f),
+            DataCache::Sparse(values) => values.entry(index).or_insert_with(f)
+        }
+    }
+}
+
+/* Zipping via the dictionaries is only worthwhile if they're no bigger than the index */
+fn can_share_index(a: &SharedIndex, b: &SharedIndex, dictionary_size: usize) -> bool {
+    dictionary_size <= a.len() && (a.same_as(b) || **a == **b)
+}
+
+/* Find the run containing position pos, if any */
+fn run_at(runs: &[(usize,usize)], pos: usize) -> Option<usize> {
+    let run = runs.partition_point(|(_,end)| *end <= pos);
+    if run < runs.len() { Some(run) } else { None }
+}
+
+pub(super) struct RunLengthBuilder(Vec<(usize,usize)>);
+
+impl RunLengthBuilder {
+    pub(super) fn new() -> RunLengthBuilder { RunLengthBuilder(vec![]) }
+
+    fn len(&self) -> usize { self.0.last().map(|(_,end)| *end).unwrap_or(0) }
+
+    pub(super) fn push(&mut self, data_index: usize, count: usize) {
+        if count == 0 { return; }
+        let end = self.len() + count;
+        if let Some((last_index,last_end)) = self.0.last_mut() {
+            if *last_index == data_index {
+                *last_end = end;
+                return;
+            }
+        }
+        self.0.push((data_index,end));
+    }
+
+    pub(super) fn make(self) -> EachOrEveryIndex {
+        EachOrEveryIndex::RunLength(Arc::new(self.0))
+    }
+}
+
+fn slice_runs(runs: &[(usize,usize)], range: Range<usize>) -> EachOrEveryIndex {
+    let mut out = RunLengthBuilder::new();
+    let mut run = runs.partition_point(|(_,end)| *end <= range.start);
+    let mut pos = range.start;
+    while pos < range.end {
+        let stop = range.end.min(runs[run].1);
+        out.push(runs[run].0,stop-pos);
+        pos = stop;
+        run += 1;
+    }
+    out.make()
+}
+
+/* Yields the data index for each position of a run-length index in turn */
+struct RunLengthIterator<'a> {
+    runs: &'a [(usize,usize)],
+    run: usize,
+    pos: usize
+}
+
+impl<'a> RunLengthIterator<'a> {
+    fn new(runs: &'a [(usize,usize)]) -> RunLengthIterator<'a> {
+        RunLengthIterator { runs, run: 0, pos: 0 }
+    }
+}
+
+impl Iterator for RunLengthIterator<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.run < self.runs.len() && self.runs[self.run].1 <= self.pos {
+            self.run += 1;
+        }
+        let (index,_) = self.runs.get(self.run)?;
+        self.pos += 1;
+        Some(*index)
+    }
+}
+
+/* Whether the result of a callback which is passed a position actually depends on it */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum EachOrEveryPositional {
+    Dependent,
+    Independent
+}
+
 #[cfg_attr(debug_assertions,derive(Debug))]
 pub struct EachOrEvery<X> {
     pub(super) index: EachOrEveryIndex,
@@ -25,13 +207,103 @@ impl<X> Clone for EachOrEvery<X> {
     }
 }
 
+/* Hash and equality are of the values at each position, whatever the representation. Every
+ * is never equal to a finite EoE. Hashing is of maximal runs of equal item hashes, so runs
+ * and constants hash in O(runs), and each data value is only hashed once, however often used.
+ */
+fn item_hash<X: Hash>(item: &X) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct HashRuns<'a,H> {
+    state: &'a mut H,
+    current: Option<(u64,usize)>
+}
+
+impl<H: Hasher> HashRuns<'_,H> {
+    fn push(&mut self, hash: u64, count: usize) {
+        if count == 0 { return; }
+        match &mut self.current {
+            Some((current_hash,current_count)) if *current_hash == hash => { *current_count += count; },
+            _ => {
+                self.flush();
+                self.current = Some((hash,count));
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some((hash,count)) = self.current.take() {
+            self.state.write_u64(hash);
+            self.state.write_usize(count);
+        }
+    }
+}
+
 impl<X: Hash> Hash for EachOrEvery<X> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.index.hash(state);
-        self.data.hash(state);
+        self.len().hash(state);
+        let mut runs = HashRuns { state, current: None };
+        let mut cache = HashMap::new();
+        let mut cached_hash = |index: usize| *cache.entry(index).or_insert_with(|| item_hash(&self.data[index]));
+        match &self.index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
+                for item in self.window_data() {
+                    runs.push(item_hash(item),1);
+                }
+            },
+            EachOrEveryIndex::Indexed(index) => {
+                for value in index.iter() {
+                    runs.push(cached_hash(*value),1);
+                }
+            },
+            EachOrEveryIndex::RunLength(index_runs) => {
+                let mut start = 0;
+                for (value,end) in index_runs.iter() {
+                    runs.push(cached_hash(*value),*end-start);
+                    start = *end;
+                }
+            },
+            EachOrEveryIndex::Repeat(len) => { runs.push(item_hash(&self.data[0]),*len); },
+            EachOrEveryIndex::Every => { runs.push(item_hash(&self.data[0]),1); }
+        }
+        runs.flush();
+    }
+}
+
+impl<X: PartialEq> EachOrEvery<X> {
+    /* Positionwise equality. With shared, positions using the same item of shared data are
+     * taken to be equal without comparing, which is only valid if equality is reflexive.
+     */
+    fn values_eq(&self, other: &Self, shared: bool) -> bool {
+        let len = self.len();
+        if len != other.len() { return false; }
+        let same_data = shared && Arc::ptr_eq(&self.data,&other.data);
+        if same_data && self.index.same_as(&other.index) { return true; }
+        if self.index.is_constant() && other.index.is_constant() {
+            return len == Some(0) || self.data[0] == other.data[0];
+        }
+        let len = len.unwrap_or(1);
+        self.index_iter(len).zip(other.index_iter(len)).all(|(a,b)| {
+            (same_data && a == b) || self.data[a] == other.data[b]
+        })
     }
 }
 
+/* Always compares values, so that eg NaN is never equal to itself, however it is stored */
+impl<X: PartialEq> PartialEq for EachOrEvery<X> {
+    fn eq(&self, other: &Self) -> bool { self.values_eq(other,false) }
+}
+
+impl<X: Eq> Eq for EachOrEvery<X> {}
+
+impl<X: Eq> EachOrEvery<X> {
+    /* As ==, but skips comparing values where data is shared */
+    pub fn content_eq(&self, other: &Self) -> bool { self.values_eq(other,true) }
+}
+
 impl<X> EachOrEvery<X> {
     pub fn each(data: Vec<X>) -> EachOrEvery<X> {
         EachOrEvery {
@@ -47,10 +319,34 @@ impl<X> EachOrEvery<X> {
         }
     }
 
+    pub fn repeat(data: X, len: usize) -> EachOrEvery<X> {
+        EachOrEvery {
+            index: EachOrEveryIndex::Repeat(len),
+            data: Arc::new(vec![data])
+        }
+    }
+
+    /* Each (value,count) pair is repeated count times. */
+    pub fn runs(mut runs: Vec<(X,usize)>) -> EachOrEvery<X> {
+        let mut index = RunLengthBuilder::new();
+        let mut data = vec![];
+        for (value,count) in runs.drain(..).filter(|(_,count)| *count > 0) {
+            index.push(data.len(),count);
+            data.push(value);
+        }
+        EachOrEvery {
+            index: index.make(),
+            data: Arc::new(data)
+        }
+    }
+
     pub fn len(&self) -> Option<usize> {
         match &self.index {
             EachOrEveryIndex::Unindexed => Some(self.data.len()),
+            EachOrEveryIndex::Window(_,len) => Some(*len),
             EachOrEveryIndex::Indexed(index) => Some(index.len()),
+            EachOrEveryIndex::RunLength(runs) => Some(runs.last().map(|(_,end)| *end).unwrap_or(0)),
+            EachOrEveryIndex::Repeat(len) => Some(*len),
             EachOrEveryIndex::Every => None
         }
     }
@@ -60,11 +356,69 @@ impl<X> EachOrEvery<X> {
     pub fn get(&self, pos: usize) -> Option<&X> {
         match &self.index {
             EachOrEveryIndex::Unindexed => self.data.get(pos),
+            EachOrEveryIndex::Window(start,len) => if pos < *len { self.data.get(start+pos) } else { None },
             EachOrEveryIndex::Indexed(index) => self.data.get(index[pos]),
+            EachOrEveryIndex::RunLength(runs) => run_at(runs,pos).and_then(|run| self.data.get(runs[run].0)),
+            EachOrEveryIndex::Repeat(len) => if pos < *len { self.data.first() } else { None },
             EachOrEveryIndex::Every => self.data.get(0)
         }
     }
 
+    /* A DataCache over our data, sized for the number of entries in our index */
+    pub(super) fn data_cache<Y>(&self) -> DataCache<Y> {
+        let lookups = match &self.index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => self.window_data().len(),
+            EachOrEveryIndex::Indexed(index) => index.len(),
+            EachOrEveryIndex::RunLength(runs) => runs.len(),
+            EachOrEveryIndex::Repeat(_) | EachOrEveryIndex::Every => 1
+        };
+        DataCache::new(self.data.len(),lookups)
+    }
+
+    /* For Unindexed and Window, the value at each position. Otherwise just the data. */
+    pub(super) fn window_data(&self) -> &[X] {
+        match &self.index {
+            EachOrEveryIndex::Window(start,len) => &self.data[*start..(*start+*len)],
+            _ => &self.data
+        }
+    }
+
+    /* Our index renumbered over only the data it references, with that data in order of first
+     * use. A window becomes unindexed. Used to write out views without their whole dictionary.
+     */
+    pub(super) fn referenced(&self) -> (EachOrEveryIndex,Vec<&X>) {
+        match &self.index {
+            EachOrEveryIndex::Indexed(index) => {
+                let mut old_to_new = self.data_cache();
+                let mut data = vec![];
+                let new_index = index.iter().map(|old| {
+                    *old_to_new.get_or_insert_with(*old,|| {
+                        data.push(&self.data[*old]);
+                        data.len()-1
+                    })
+                }).collect::<Vec<_>>();
+                (EachOrEveryIndex::Indexed(SharedIndex::new(new_index)),data)
+            },
+            EachOrEveryIndex::RunLength(runs) => {
+                let mut old_to_new = self.data_cache();
+                let mut data = vec![];
+                let mut new_runs = RunLengthBuilder::new();
+                let mut start = 0;
+                for (old,end) in runs.iter() {
+                    let new = *old_to_new.get_or_insert_with(*old,|| {
+                        data.push(&self.data[*old]);
+                        data.len()-1
+                    });
+                    new_runs.push(new,*end-start);
+                    start = *end;
+                }
+                (new_runs.make(),data)
+            },
+            EachOrEveryIndex::Window(_,_) => (EachOrEveryIndex::Unindexed,self.window_data().iter().collect()),
+            _ => (self.index.clone(),self.data.iter().collect())
+        }
+    }
+
     fn unsquash<F,K: Clone+Hash+Eq>(&self, cb: F) -> (Vec<(K,EachOrEveryFilterBuilder)>,Vec<usize>) where F: Fn(&X) -> K {
         /* Optimised hot-path: main objsective is to minimise operations done per index,
          * iterating as much as we can only over data instead. This will be much smaller
@@ -73,7 +427,7 @@ impl<X> EachOrEvery<X> {
         let mut builders = vec![];
         let mut key_to_builder = HashMap::new();
         let mut builder_choices = vec![];
-        for key in self.data.iter().map(cb) {
+        for key in self.window_data().iter().map(cb) {
             if let Some(choice) = key_to_builder.get(&key) {
                 builder_choices.push(*choice);
             } else {
@@ -87,7 +441,7 @@ impl<X> EachOrEvery<X> {
 
     pub fn demerge<F,K: Clone+Hash+Eq>(&self, len: usize, cb: F) -> Vec<(K,EachOrEveryFilter)> where F: Fn(&X) -> K {
         match &self.index {
-            EachOrEveryIndex::Unindexed => {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
                 let (mut out,mapped_dest) = self.unsquash(cb);
                 for (i,value) in mapped_dest.iter().enumerate() {
                     out[*value].1.set(i);
@@ -101,30 +455,118 @@ impl<X> EachOrEvery<X> {
                 }
                 out.drain(..).map(|(key,filter)| (key,filter.make(len))).collect::<Vec<_>>()
             },
+            EachOrEveryIndex::RunLength(runs) => {
+                let (mut out,mapped_dest) = self.unsquash(cb);
+                let mut start = 0;
+                for (value,end) in runs.iter() {
+                    out[mapped_dest[*value]].1.set_run(start,*end-start);
+                    start = *end;
+                }
+                out.drain(..).map(|(key,filter)| (key,filter.make(len))).collect::<Vec<_>>()
+            },
+            EachOrEveryIndex::Repeat(_) |
             EachOrEveryIndex::Every => vec![(cb(&self.data[0]),EachOrEveryFilter::all(len))]
         }
     }
 
+    /* Calls cb with each data item in use over len positions and the number of positions
+     * which use it, in order of first use. Unindexed data is walked directly without any
+     * bookkeeping. Caller must check len is compatible.
+     */
+    pub(super) fn data_usage<F>(&self, len: usize, mut cb: F) where F: FnMut(&X,usize) {
+        match &self.index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
+                for item in self.window_data() {
+                    cb(item,1);
+                }
+                return;
+            },
+            EachOrEveryIndex::Repeat(_) |
+            EachOrEveryIndex::Every => {
+                if len > 0 { cb(&self.data[0],len); }
+                return;
+            },
+            _ => {}
+        }
+        let mut slots = self.data_cache();
+        let mut usage : Vec<(usize,usize)> = vec![];
+        let mut add = |value: usize, count: usize| {
+            let slot = *slots.get_or_insert_with(value,|| {
+                usage.push((value,0));
+                usage.len()-1
+            });
+            usage[slot].1 += count;
+        };
+        match &self.index {
+            EachOrEveryIndex::Indexed(index) => {
+                for value in index.iter() {
+                    add(*value,1);
+                }
+            },
+            EachOrEveryIndex::RunLength(runs) => {
+                let mut start = 0;
+                for (value,end) in runs.iter() {
+                    add(*value,*end-start);
+                    start = *end;
+                }
+            },
+            _ => {}
+        }
+        for (value,count) in usage {
+            cb(&self.data[value],count);
+        }
+    }
+
+    /* Number of positions with each distinct key, in order of first appearance. */
+    pub fn value_counts<F,K: Clone+Hash+Eq>(&self, len: usize, cb: F) -> Vec<(K,usize)> where F: Fn(&X) -> K {
+        let mut out : Vec<(K,usize)> = vec![];
+        let mut key_to_out : HashMap<K,usize> = HashMap::new();
+        self.data_usage(len,|item,count| {
+            let key = cb(item);
+            if let Some(pos) = key_to_out.get(&key) {
+                out[*pos].1 += count;
+            } else {
+                key_to_out.insert(key.clone(),out.len());
+                out.push((key,count));
+            }
+        });
+        out
+    }
+
+    /* As value_counts, but ordered by key. */
+    pub fn value_counts_sorted<F,K: Clone+Hash+Ord>(&self, len: usize, cb: F) -> Vec<(K,usize)> where F: Fn(&X) -> K {
+        let mut out = self.value_counts(len,cb);
+        out.sort_by(|a,b| a.0.cmp(&b.0));
+        out
+    }
+
     pub fn map<F,Y>(&self, mut f: F) -> EachOrEvery<Y> where F: FnMut(&X) -> Y {
         /* not using functional style because code path is hot */
-        let mut new_data = Vec::with_capacity(self.data.len());
-        for e in self.data.iter() {
+        let data = self.window_data();
+        let mut new_data = Vec::with_capacity(data.len());
+        for e in data.iter() {
             new_data.push(f(e));
         }
         EachOrEvery {
-            index: self.index.clone(),
+            index: self.index.unwindowed(),
             data: Arc::new(new_data)
         }
     }
     
     pub fn map_mut<F>(&mut self, f: F) where F: FnMut(&X) -> X {
-        self.data = Arc::new(self.data.iter().map(f).collect::<Vec<_>>());
+        self.data = Arc::new(self.window_data().iter().map(f).collect::<Vec<_>>());
+        self.index = self.index.unwindowed();
     }
 
     pub fn fold_mut<F,Z>(&mut self, data: &[Z], f: F) where F: Fn(&X,&Z) -> X {
         match &self.index {
-            EachOrEveryIndex::Every | EachOrEveryIndex::Unindexed => {
-                self.data = Arc::new(self.data.iter().zip(data.iter().cycle()).map(|(x,z)| f(x,z)).collect::<Vec<_>>());
+            EachOrEveryIndex::Every | EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
+                self.data = Arc::new(self.window_data().iter().zip(data.iter().cycle()).map(|(x,z)| f(x,z)).collect::<Vec<_>>());
+                self.index = self.index.unwindowed();
+            },
+            EachOrEveryIndex::Repeat(len) => {
+                self.data = Arc::new(data.iter().take(*len).map(|z| f(&self.data[0],z)).collect::<Vec<_>>());
+                self.index = EachOrEveryIndex::Unindexed;
             },
             EachOrEveryIndex::Indexed(index) => {
                 let mut out = vec![];
@@ -133,38 +575,60 @@ impl<X> EachOrEvery<X> {
                 }
                 self.data = Arc::new(out);
                 self.index = EachOrEveryIndex::Unindexed;
+            },
+            EachOrEveryIndex::RunLength(runs) => {
+                let mut out = vec![];
+                for (i,z) in RunLengthIterator::new(runs).zip(data.iter()) {
+                    out.push(f(&self.data[i],z));
+                }
+                self.data = Arc::new(out);
+                self.index = EachOrEveryIndex::Unindexed;
             }
         }
     }
 
     pub fn map_results<F,Y,E>(&self, f: F) -> Result<EachOrEvery<Y>,E> where F: FnMut(&X) -> Result<Y,E> {
-        let data = self.data.iter().map(f).collect::<Result<_,_>>()?;
+        let data = self.window_data().iter().map(f).collect::<Result<_,_>>()?;
         Ok(EachOrEvery {
-            index: self.index.clone(),
+            index: self.index.unwindowed(),
             data: Arc::new(data)
         })
     }
 
     fn inner_zip<W,F,Y>(&self, other: &EachOrEvery<Y>, cb: F) -> EachOrEvery<W> where F: Fn(&X,&Y) -> W {
         match (&self.index,&other.index) {
-            (x,EachOrEveryIndex::Every) => {
+            (x,EachOrEveryIndex::Every) |
+            (x,EachOrEveryIndex::Repeat(_)) => {
                 EachOrEvery {
-                    index: x.clone(),
-                    data: Arc::new(self.data.iter().map(|a| cb(a,&other.data[0])).collect())
+                    index: x.unwindowed(),
+                    data: Arc::new(self.window_data().iter().map(|a| cb(a,&other.data[0])).collect())
                 }
             },
 
-            (EachOrEveryIndex::Unindexed, EachOrEveryIndex::Unindexed) => {
+            (EachOrEveryIndex::Unindexed, EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::Unindexed, EachOrEveryIndex::Window(_,_)) |
+            (EachOrEveryIndex::Window(_,_), EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::Window(_,_), EachOrEveryIndex::Window(_,_)) => {
                 EachOrEvery {
                     index: EachOrEveryIndex::Unindexed,
-                    data: Arc::new(self.data.iter().zip(other.data.iter()).map(|(a,b)| cb(a,b)).collect())
+                    data: Arc::new(self.window_data().iter().zip(other.window_data().iter()).map(|(a,b)| cb(a,b)).collect())
                 }
             },
 
-            (EachOrEveryIndex::Indexed(index), EachOrEveryIndex::Unindexed) => {
+            (EachOrEveryIndex::Indexed(index), EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::Indexed(index), EachOrEveryIndex::Window(_,_)) => {
                 EachOrEvery {
                     index: EachOrEveryIndex::Unindexed,
-                    data: Arc::new(index.iter().zip(other.data.iter()).map(|(a,b)| cb(&self.data[*a],b)).collect())
+                    data: Arc::new(index.iter().zip(other.window_data().iter()).map(|(a,b)| cb(&self.data[*a],b)).collect())
+                }
+            },
+
+            (EachOrEveryIndex::Indexed(self_index), EachOrEveryIndex::Indexed(other_index))
+                    if can_share_index(self_index,other_index,self.data.len().min(other.data.len())) => {
+                /* eg filtered by the same filter: positions use the same entry in each dictionary */
+                EachOrEvery {
+                    index: self.index.clone(),
+                    data: Arc::new(self.data.iter().zip(other.data.iter()).map(|(a,b)| cb(a,b)).collect())
                 }
             },
 
@@ -175,15 +639,57 @@ impl<X> EachOrEvery<X> {
                 }
             },
 
+            (EachOrEveryIndex::RunLength(runs), EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::RunLength(runs), EachOrEveryIndex::Window(_,_)) => {
+                EachOrEvery {
+                    index: EachOrEveryIndex::Unindexed,
+                    data: Arc::new(RunLengthIterator::new(runs).zip(other.window_data().iter()).map(|(a,b)| cb(&self.data[a],b)).collect())
+                }
+            },
+
+            (EachOrEveryIndex::RunLength(runs), EachOrEveryIndex::Indexed(other_index)) => {
+                EachOrEvery {
+                    index: EachOrEveryIndex::Unindexed,
+                    data: Arc::new(RunLengthIterator::new(runs).zip(other_index.iter()).map(|(a,b)| cb(&self.data[a],&other.data[*b])).collect())
+                }
+            },
+
+            (EachOrEveryIndex::RunLength(self_runs), EachOrEveryIndex::RunLength(other_runs)) => {
+                /* one output run for each place where either input starts a new run */
+                let mut index = RunLengthBuilder::new();
+                let mut data = vec![];
+                let (mut a,mut b,mut pos) = (0,0,0);
+                while a < self_runs.len() && b < other_runs.len() {
+                    let end = self_runs[a].1.min(other_runs[b].1);
+                    index.push(data.len(),end-pos);
+                    data.push(cb(&self.data[self_runs[a].0],&other.data[other_runs[b].0]));
+                    if self_runs[a].1 == end { a += 1; }
+                    if other_runs[b].1 == end { b += 1; }
+                    pos = end;
+                }
+                EachOrEvery { index: index.make(), data: Arc::new(data) }
+            },
+
             _ => panic!()
         }
     }
 
     pub fn zip<W,F,Y>(&self, other: &EachOrEvery<Y>, cb: F) -> EachOrEvery<W> where F: Fn(&X,&Y) -> W {
         match (&self.index,&other.index) {
+            (EachOrEveryIndex::Every, EachOrEveryIndex::Repeat(_)) |
             (EachOrEveryIndex::Every, EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::Every, EachOrEveryIndex::Window(_,_)) |
             (EachOrEveryIndex::Every, EachOrEveryIndex::Indexed(_)) |
-            (EachOrEveryIndex::Unindexed, EachOrEveryIndex::Indexed(_)) => 
+            (EachOrEveryIndex::Every, EachOrEveryIndex::RunLength(_)) |
+            (EachOrEveryIndex::Repeat(_), EachOrEveryIndex::Unindexed) |
+            (EachOrEveryIndex::Repeat(_), EachOrEveryIndex::Window(_,_)) |
+            (EachOrEveryIndex::Repeat(_), EachOrEveryIndex::Indexed(_)) |
+            (EachOrEveryIndex::Repeat(_), EachOrEveryIndex::RunLength(_)) |
+            (EachOrEveryIndex::Unindexed, EachOrEveryIndex::Indexed(_)) |
+            (EachOrEveryIndex::Unindexed, EachOrEveryIndex::RunLength(_)) |
+            (EachOrEveryIndex::Window(_,_), EachOrEveryIndex::Indexed(_)) |
+            (EachOrEveryIndex::Window(_,_), EachOrEveryIndex::RunLength(_)) |
+            (EachOrEveryIndex::Indexed(_), EachOrEveryIndex::RunLength(_)) => 
                 other.inner_zip(self,|a,b| cb(b,a)),
 
             _ =>
@@ -196,17 +702,26 @@ impl<X> EachOrEvery<X> {
             if self_len != len { return None; }
         }
         Some(EachOrEveryIterator {
-            obj: self,
-            index: 0,
-            len
+            data: &self.data,
+            index: self.index_iter(len)
         })
     }
 
+    /* Data index for each of the first len positions. Caller must check len is compatible. */
+    pub(super) fn index_iter(&self, len: usize) -> EachOrEveryIndexIterator<'_> {
+        EachOrEveryIndexIterator {
+            index: &self.index,
+            pos: 0,
+            run: 0,
+            len
+        }
+    }
+
     pub fn make_filter<F>(&self, len: usize, cb: F) -> EachOrEveryFilter where F: Fn(&X) -> bool {
         match &self.index {
-            EachOrEveryIndex::Unindexed => {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
                 let mut filter = EachOrEveryFilterBuilder::new();
-                for (i,value) in self.data.iter().enumerate() {
+                for (i,value) in self.window_data().iter().enumerate() {
                     if cb(value) {
                         filter.set(i);
                     }
@@ -214,14 +729,28 @@ impl<X> EachOrEvery<X> {
                 filter.make(len)
             },
             EachOrEveryIndex::Indexed(index) => {
+                /* the predicate only depends on the data, so call it once per item in use */
+                let mut results = self.data_cache();
                 let mut filter = EachOrEveryFilterBuilder::new();
                 for (i,value) in index.iter().enumerate() {
-                    if cb(&self.data[*value]) {
+                    if *results.get_or_insert_with(*value,|| cb(&self.data[*value])) {
                         filter.set(i);
                     }
                 }
                 filter.make(len)
             },
+            EachOrEveryIndex::RunLength(runs) => {
+                let mut filter = EachOrEveryFilterBuilder::new();
+                let mut start = 0;
+                for (value,end) in runs.iter() {
+                    if cb(&self.data[*value]) {
+                        filter.set_run(start,*end-start);
+                    }
+                    start = *end;
+                }
+                filter.make(len)
+            },
+            EachOrEveryIndex::Repeat(_) |
             EachOrEveryIndex::Every => {
                 if cb(&self.data[0]) {
                     EachOrEveryFilter::all(len)
@@ -232,23 +761,167 @@ impl<X> EachOrEvery<X> {
         }
     }
 
+    /* The result of cb for each position, calling it only once for each data item in use.
+     * None if len is incompatible.
+     */
+    pub fn map_positions<F,Y: Clone>(&self, len: usize, cb: F) -> Option<Vec<Y>> where F: Fn(&X) -> Y {
+        if !self.compatible(len) { return None; }
+        Some(match &self.index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
+                self.window_data().iter().map(cb).collect()
+            },
+            _ => {
+                let mut results = self.data_cache();
+                self.index_iter(len).map(|i| {
+                    results.get_or_insert_with(i,|| cb(&self.data[i])).clone()
+                }).collect()
+            }
+        })
+    }
+
+    /* As map but f is also passed the position. When Dependent, f is called at every position
+     * and the output is unindexed. When Independent, f is called only at the first position
+     * using each data item and the output keeps our index (Every stays Every). None if len
+     * is incompatible.
+     */
+    pub fn map_with_position<F,Y>(&self, len: usize, positional: EachOrEveryPositional, f: F) -> Option<EachOrEvery<Y>> where F: Fn(usize,&X) -> Y {
+        if !self.compatible(len) { return None; }
+        Some(match (&self.index,positional) {
+            (EachOrEveryIndex::Repeat(_),EachOrEveryPositional::Independent) |
+            (EachOrEveryIndex::Every,EachOrEveryPositional::Independent) => {
+                EachOrEvery { index: self.index.clone(), data: Arc::new(vec![f(0,&self.data[0])]) }
+            },
+            (EachOrEveryIndex::Indexed(index),EachOrEveryPositional::Independent) => {
+                let mut old_to_new = self.data_cache();
+                let mut data = vec![];
+                let new_index = index.iter().enumerate().map(|(pos,old)| {
+                    *old_to_new.get_or_insert_with(*old,|| {
+                        data.push(f(pos,&self.data[*old]));
+                        data.len()-1
+                    })
+                }).collect::<Vec<_>>();
+                EachOrEvery { index: EachOrEveryIndex::Indexed(SharedIndex::new(new_index)), data: Arc::new(data) }
+            },
+            (EachOrEveryIndex::RunLength(runs),EachOrEveryPositional::Independent) => {
+                let mut old_to_new = self.data_cache();
+                let mut data = vec![];
+                let mut new_runs = RunLengthBuilder::new();
+                let mut start = 0;
+                for (old,end) in runs.iter() {
+                    let new = *old_to_new.get_or_insert_with(*old,|| {
+                        data.push(f(start,&self.data[*old]));
+                        data.len()-1
+                    });
+                    new_runs.push(new,*end-start);
+                    start = *end;
+                }
+                EachOrEvery { index: new_runs.make(), data: Arc::new(data) }
+            },
+            _ => {
+                EachOrEvery::each(self.index_iter(len).enumerate().map(|(pos,index)| f(pos,&self.data[index])).collect())
+            }
+        })
+    }
+
+    /* As zip but f is also passed the position. When Dependent, f is called at every position
+     * and the output is unindexed. When Independent, f is called only at the first position
+     * using each pair of data items, so the cost is in proportion to len and not to the size
+     * of either dictionary. None if len is incompatible with either input.
+     */
+    pub fn zip_with_position<W,F,Y>(&self, other: &EachOrEvery<Y>, len: usize, positional: EachOrEveryPositional, f: F) -> Option<EachOrEvery<W>> where F: Fn(usize,&X,&Y) -> W {
+        if !self.compatible(len) || !other.compatible(len) { return None; }
+        let pairs = self.index_iter(len).zip(other.index_iter(len)).enumerate();
+        match positional {
+            EachOrEveryPositional::Dependent => {
+                Some(EachOrEvery::each(pairs.map(|(pos,(a,b))| f(pos,&self.data[a],&other.data[b])).collect()))
+            },
+            EachOrEveryPositional::Independent => {
+                if self.index.is_constant() && other.index.is_constant() {
+                    let index = match (&self.index,&other.index) {
+                        (EachOrEveryIndex::Every,EachOrEveryIndex::Every) => EachOrEveryIndex::Every,
+                        _ => EachOrEveryIndex::Repeat(len)
+                    };
+                    return Some(EachOrEvery { index, data: Arc::new(vec![f(0,&self.data[0],&other.data[0])]) });
+                }
+                let mut pair_to_new = HashMap::new();
+                let mut data = vec![];
+                let index = pairs.map(|(pos,(a,b))| {
+                    *pair_to_new.entry((a,b)).or_insert_with(|| {
+                        data.push(f(pos,&self.data[a],&other.data[b]));
+                        data.len()-1
+                    })
+                }).collect::<Vec<_>>();
+                /* every pair distinct, so data is already in position order */
+                if data.len() == len { return Some(EachOrEvery::each(data)); }
+                Some(EachOrEvery { index: EachOrEveryIndex::Indexed(SharedIndex::new(index)), data: Arc::new(data) })
+            }
+        }
+    }
+
     pub fn filter(&self, data_filter: &EachOrEveryFilter) -> EachOrEvery<X> {
         data_filter.eoe_filter(self)
     }
 
+    /* Values at the given positions, which may be in any order and repeat. None if any is out
+     * of range. Data is shared, only a new index is built.
+     */
+    pub fn gather(&self, positions: &[usize]) -> Option<EachOrEvery<X>> {
+        if let Some(len) = self.len() {
+            if positions.iter().any(|pos| *pos >= len) { return None; }
+        }
+        Some(self.reindex(&Arc::new(positions.to_vec())))
+    }
+
+    /* Positions in range, without copying data or index. None if the range is out of bounds. */
+    pub fn slice(&self, range: Range<usize>) -> Option<EachOrEvery<X>> {
+        if range.start > range.end { return None; }
+        if let Some(len) = self.len() {
+            if range.end > len { return None; }
+        }
+        let len = range.end - range.start;
+        let index = match &self.index {
+            EachOrEveryIndex::Unindexed => EachOrEveryIndex::Window(range.start,len),
+            EachOrEveryIndex::Window(start,_) => EachOrEveryIndex::Window(start+range.start,len),
+            EachOrEveryIndex::Indexed(index) => EachOrEveryIndex::Indexed(index.slice(range)),
+            EachOrEveryIndex::RunLength(runs) => slice_runs(runs,range),
+            EachOrEveryIndex::Repeat(_) => EachOrEveryIndex::Repeat(len),
+            EachOrEveryIndex::Every => EachOrEveryIndex::Every
+        };
+        Some(EachOrEvery { index, data: self.data.clone() })
+    }
+
+    /* New EoE sharing our data with the values at the given positions (which must be in range) */
+    pub(super) fn reindex(&self, positions: &Arc<Vec<usize>>) -> EachOrEvery<X> {
+        let index = match &self.index {
+            EachOrEveryIndex::Unindexed => EachOrEveryIndex::Indexed(SharedIndex::from_arc(positions.clone())),
+            EachOrEveryIndex::Window(start,_) => {
+                EachOrEveryIndex::Indexed(SharedIndex::new(positions.iter().map(|pos| start+*pos).collect()))
+            },
+            EachOrEveryIndex::Indexed(index) => {
+                EachOrEveryIndex::Indexed(SharedIndex::new(positions.iter().map(|pos| index[*pos]).collect()))
+            },
+            EachOrEveryIndex::RunLength(runs) => {
+                EachOrEveryIndex::Indexed(SharedIndex::new(positions.iter().map(|pos| runs[run_at(runs,*pos).unwrap()].0).collect()))
+            },
+            EachOrEveryIndex::Repeat(_) => EachOrEveryIndex::Repeat(positions.len()),
+            EachOrEveryIndex::Every => EachOrEveryIndex::Every
+        };
+        EachOrEvery { index, data: self.data.clone() }
+    }
+
     pub fn to_each(&self, len: usize) -> Option<EachOrEvery<X>> {
         match &self.index {
             EachOrEveryIndex::Every => {
                 Some(EachOrEvery {
-                    index: EachOrEveryIndex::Indexed(Arc::new(vec![0;len])),
+                    index: EachOrEveryIndex::Repeat(len),
                     data: self.data.clone()
                 })
             },
             EachOrEveryIndex::Unindexed => {
                 if self.data.len() == len { Some(self.clone()) } else { None }
             },
-            EachOrEveryIndex::Indexed(index) => {
-                if index.len() == len { Some(self.clone()) } else { None }
+            EachOrEveryIndex::Window(_,_) | EachOrEveryIndex::Indexed(_) | EachOrEveryIndex::RunLength(_) | EachOrEveryIndex::Repeat(_) => {
+                if self.len() == Some(len) { Some(self.clone()) } else { None }
             }
         }
     }
@@ -270,7 +943,7 @@ impl<X: Clone> EachOrEvery<X> {
         let mut index = vec![];
         let mut data = vec![];
         let mut map = HashMap::new();
-        for item in self.data.iter() {
+        for item in self.window_data().iter() {
             let x = cb(item);
             if let Some(pos) = map.get(&x).copied() {
                 index.push(pos);
@@ -285,10 +958,10 @@ impl<X: Clone> EachOrEvery<X> {
 
     pub fn index<F,Z>(&self, cb: F) -> EachOrEvery<X> where F: Fn(&X) -> Z, Z: Eq+Hash {
         match &self.index {
-            EachOrEveryIndex::Unindexed => {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
                 let (index,data) = self.squash(cb);
                 EachOrEvery {
-                    index: EachOrEveryIndex::Indexed(Arc::new(index)),
+                    index: EachOrEveryIndex::Indexed(SharedIndex::new(index)),
                     data: Arc::new(data)
                 }
             },
@@ -296,33 +969,325 @@ impl<X: Clone> EachOrEvery<X> {
                 let (old_to_new,data) = self.squash(cb);
                 let index : Vec<_> = old_index.iter().map(|old| old_to_new[*old]).collect();
                 EachOrEvery {
-                    index: EachOrEveryIndex::Indexed(Arc::new(index)),
+                    index: EachOrEveryIndex::Indexed(SharedIndex::new(index)),
                     data: Arc::new(data)
                 }
             },
+            EachOrEveryIndex::RunLength(old_runs) => {
+                let (old_to_new,data) = self.squash(cb);
+                EachOrEvery {
+                    index: remap_runs(old_runs,&old_to_new),
+                    data: Arc::new(data)
+                }
+            },
+            EachOrEveryIndex::Repeat(_) |
             EachOrEveryIndex::Every => { self.clone() }
         }
     }
+
+    /* Drop data no longer referenced (eg after filtering) and renumber the index. Indexed
+     * EoEs become unindexed if that would take less memory.
+     */
+    pub fn compact(&self) -> EachOrEvery<X> {
+        if let EachOrEveryIndex::Unindexed | EachOrEveryIndex::Repeat(_) | EachOrEveryIndex::Every = &self.index {
+            return self.clone();
+        }
+        let (index,data) = self.referenced();
+        if let EachOrEveryIndex::Indexed(new_index) = &index {
+            let value_size = std::mem::size_of::<X>();
+            let unindexed_size = new_index.len() * value_size;
+            let indexed_size = data.len() * value_size + new_index.len() * std::mem::size_of::<usize>();
+            if unindexed_size <= indexed_size {
+                return EachOrEvery::each(new_index.iter().map(|i| data[*i].clone()).collect());
+            }
+        }
+        EachOrEvery { index, data: Arc::new(data.into_iter().cloned().collect()) }
+    }
+
+    /* compact() but only if more than half of the data is unused. This is only an estimate,
+     * to keep the test O(1): the number of positions bounds the data which can be in use.
+     */
+    pub fn auto_compact(&self) -> EachOrEvery<X> {
+        let used = match &self.index {
+            EachOrEveryIndex::Indexed(_) | EachOrEveryIndex::Window(_,_) => self.len().unwrap_or(0),
+            EachOrEveryIndex::RunLength(runs) => runs.len(),
+            _ => { return self.clone(); }
+        };
+        if used * 2 < self.data.len() { self.compact() } else { self.clone() }
+    }
+
+    /* As index() but the result is run-length encoded. Best when equal values come in long runs. */
+    pub fn run_length<F,Z>(&self, cb: F) -> EachOrEvery<X> where F: Fn(&X) -> Z, Z: Eq+Hash {
+        let (old_to_new,data) = self.squash(cb);
+        let mut runs = RunLengthBuilder::new();
+        match &self.index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => {
+                for new in old_to_new.iter() { runs.push(*new,1); }
+            },
+            EachOrEveryIndex::Indexed(old_index) => {
+                for old in old_index.iter() { runs.push(old_to_new[*old],1); }
+            },
+            EachOrEveryIndex::RunLength(old_runs) => {
+                return EachOrEvery { index: remap_runs(old_runs,&old_to_new), data: Arc::new(data) };
+            },
+            EachOrEveryIndex::Repeat(_) |
+            EachOrEveryIndex::Every => { return self.clone(); }
+        }
+        EachOrEvery {
+            index: runs.make(),
+            data: Arc::new(data)
+        }
+    }
+
+    /* Join parts end to end. Each part comes with its length, as Every has none. None if any
+     * part has the wrong length. The result stays Every or Repeat if all parts are the same
+     * constant, is unindexed if all parts are, and otherwise uses one dictionary merged using
+     * cb as in index() (run-length encoded if all parts are runs or constants).
+     */
+    pub fn concat<F,Z>(parts: &[(&EachOrEvery<X>,usize)], cb: F) -> Option<EachOrEvery<X>> where F: Fn(&X) -> Z, Z: Eq+Hash {
+        if parts.iter().any(|(part,len)| !part.compatible(*len)) { return None; }
+        /* empty parts contribute nothing, so mustn't affect the choice of representation */
+        let parts = parts.iter().filter(|(_,len)| *len > 0).cloned().collect::<Vec<_>>();
+        let total = parts.iter().map(|(_,len)| *len).sum();
+        let all = |test: fn(&EachOrEveryIndex) -> bool| parts.iter().all(|(part,_)| test(&part.index));
+        if all(|x| matches!(x,EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_))) {
+            let mut data = Vec::with_capacity(total);
+            for (part,_) in parts.iter() {
+                data.extend_from_slice(part.window_data());
+            }
+            return Some(EachOrEvery::each(data));
+        }
+        if all(|x| matches!(x,EachOrEveryIndex::Every | EachOrEveryIndex::Repeat(_))) && {
+                let first = cb(&parts[0].0.data[0]);
+                parts.iter().all(|(part,_)| cb(&part.data[0]) == first)
+            } {
+            let index = if all(|x| matches!(x,EachOrEveryIndex::Every)) {
+                EachOrEveryIndex::Every
+            } else {
+                EachOrEveryIndex::Repeat(total)
+            };
+            return Some(EachOrEvery { index, data: parts[0].0.data.clone() });
+        }
+        let mut dictionary = DictionaryBuilder::new();
+        let index = if all(|x| matches!(x,EachOrEveryIndex::RunLength(_) | EachOrEveryIndex::Repeat(_) | EachOrEveryIndex::Every)) {
+            let mut runs = RunLengthBuilder::new();
+            for (part,len) in parts.iter() {
+                let mut old_to_new = part.data_cache();
+                if let EachOrEveryIndex::RunLength(part_runs) = &part.index {
+                    let mut start = 0;
+                    for (old,end) in part_runs.iter() {
+                        runs.push(dictionary.add_lazy(&part.data,&mut old_to_new,*old,&cb),*end-start);
+                        start = *end;
+                    }
+                } else {
+                    runs.push(dictionary.add_lazy(&part.data,&mut old_to_new,0,&cb),*len);
+                }
+            }
+            runs.make()
+        } else {
+            let mut index = Vec::with_capacity(total);
+            for (part,len) in parts.iter() {
+                let mut old_to_new = part.data_cache();
+                for old in part.index_iter(*len) {
+                    index.push(dictionary.add_lazy(&part.data,&mut old_to_new,old,&cb));
+                }
+            }
+            EachOrEveryIndex::Indexed(SharedIndex::new(index))
+        };
+        Some(EachOrEvery { index, data: Arc::new(dictionary.data) })
+    }
 }
 
-pub struct EachOrEveryIterator<'a,X> {
-    obj: &'a EachOrEvery<X>,
-    index: usize,
+/* Accumulates distinct values as identified by a key, as in squash() */
+struct DictionaryBuilder<X,Z> {
+    map: HashMap<Z,usize>,
+    data: Vec<X>
+}
+
+impl<X: Clone,Z: Eq+Hash> DictionaryBuilder<X,Z> {
+    fn new() -> DictionaryBuilder<X,Z> {
+        DictionaryBuilder { map: HashMap::new(), data: vec![] }
+    }
+
+    fn add<F>(&mut self, item: &X, cb: F) -> usize where F: Fn(&X) -> Z {
+        let key = cb(item);
+        if let Some(pos) = self.map.get(&key) { return *pos; }
+        self.map.insert(key,self.data.len());
+        self.data.push(item.clone());
+        self.data.len()-1
+    }
+
+    /* Only data actually referenced is added, hence the lazy old to new mapping */
+    fn add_lazy<F>(&mut self, data: &[X], old_to_new: &mut DataCache<usize>, old: usize, cb: F) -> usize where F: Fn(&X) -> Z {
+        *old_to_new.get_or_insert_with(old,|| self.add(&data[old],cb))
+    }
+}
+
+/* Renumber the data indexes of some runs, merging any which become adjacent and equal */
+fn remap_runs(runs: &[(usize,usize)], old_to_new: &[usize]) -> EachOrEveryIndex {
+    let mut out = RunLengthBuilder::new();
+    let mut start = 0;
+    for (old,end) in runs.iter() {
+        out.push(old_to_new[*old],*end-start);
+        start = *end;
+    }
+    out.make()
+}
+
+pub(super) struct EachOrEveryIndexIterator<'a> {
+    index: &'a EachOrEveryIndex,
+    pos: usize,
+    run: usize,
     len: usize
 }
 
+impl Iterator for EachOrEveryIndexIterator<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.pos == self.len { return None; }
+        let out = match self.index {
+            EachOrEveryIndex::Unindexed => self.pos,
+            EachOrEveryIndex::Window(start,_) => start+self.pos,
+            EachOrEveryIndex::Indexed(index) => index[self.pos],
+            EachOrEveryIndex::RunLength(runs) => {
+                while runs[self.run].1 <= self.pos { self.run += 1; }
+                runs[self.run].0
+            },
+            EachOrEveryIndex::Repeat(_) |
+            EachOrEveryIndex::Every => 0
+        };
+        self.pos += 1;
+        Some(out)
+    }
+}
+
+pub struct EachOrEveryIterator<'a,X> {
+    data: &'a [X],
+    index: EachOrEveryIndexIterator<'a>
+}
+
 impl<'a,X> Iterator for EachOrEveryIterator<'a,X> {
     type Item = &'a X;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index == self.len { return None; }
-        let out = match &self.obj.index {
-            EachOrEveryIndex::Unindexed => &self.obj.data[self.index],
-            EachOrEveryIndex::Indexed(index) => &self.obj.data[index[self.index]],
-            EachOrEveryIndex::Every => &self.obj.data[0]
-        };
-        self.index += 1;
-        Some(out)
+        self.index.next().map(|index| &self.data[index])
+    }
+}
+
+/* A column made of several EoEs end to end (eg one per tile), without copying them into one.
+ * Every chunk has a known length: Every parts are stored as repeats.
+ */
+#[cfg_attr(debug_assertions,derive(Debug))]
+pub struct EachOrEveryChunked<X> {
+    chunks: Vec<EachOrEvery<X>>,
+    ends: Vec<usize>
+}
+
+impl<X> Clone for EachOrEveryChunked<X> {
+    fn clone(&self) -> Self {
+        Self { chunks: self.chunks.clone(), ends: self.ends.clone() }
+    }
+}
+
+impl<X> Default for EachOrEveryChunked<X> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<X> EachOrEveryChunked<X> {
+    pub fn new() -> EachOrEveryChunked<X> {
+        EachOrEveryChunked { chunks: vec![], ends: vec![] }
+    }
+
+    /* Returns false (and adds nothing) if chunk doesn't have length len */
+    pub fn push(&mut self, chunk: &EachOrEvery<X>, len: usize) -> bool {
+        let chunk = if let Some(chunk) = chunk.to_each(len) { chunk } else { return false; };
+        self.ends.push(self.len()+len);
+        self.chunks.push(chunk);
+        true
+    }
+
+    pub fn len(&self) -> usize { self.ends.last().copied().unwrap_or(0) }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    pub fn chunks(&self) -> &[EachOrEvery<X>] { &self.chunks }
+
+    fn start(&self, chunk: usize) -> usize {
+        if chunk > 0 { self.ends[chunk-1] } else { 0 }
+    }
+
+    fn ranges(&self) -> impl Iterator<Item=Range<usize>> + '_ {
+        (0..self.chunks.len()).map(move |i| self.start(i)..self.ends[i])
+    }
+
+    fn from_chunks(chunks: Vec<EachOrEvery<X>>, lens: impl Iterator<Item=usize>) -> EachOrEveryChunked<X> {
+        let mut end = 0;
+        let ends = lens.map(|len| { end += len; end }).collect();
+        EachOrEveryChunked { chunks, ends }
+    }
+
+    /* range must not cross a chunk boundary */
+    fn within_chunk(&self, range: Range<usize>) -> EachOrEvery<X> {
+        let chunk = self.ends.partition_point(|end| *end <= range.start);
+        let offset = self.start(chunk);
+        self.chunks[chunk].slice((range.start-offset)..(range.end-offset)).unwrap()
+    }
+
+    pub fn get(&self, pos: usize) -> Option<&X> {
+        let chunk = self.ends.partition_point(|end| *end <= pos);
+        self.chunks.get(chunk).and_then(|eoe| eoe.get(pos-self.start(chunk)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&X> {
+        self.chunks.iter().zip(self.ranges()).flat_map(|(chunk,range)| chunk.iter(range.len()).unwrap())
+    }
+
+    pub fn map<F,Y>(&self, mut f: F) -> EachOrEveryChunked<Y> where F: FnMut(&X) -> Y {
+        EachOrEveryChunked {
+            chunks: self.chunks.iter().map(|chunk| chunk.map(&mut f)).collect(),
+            ends: self.ends.clone()
+        }
+    }
+
+    pub fn filter(&self, filter: &EachOrEveryFilter) -> EachOrEveryChunked<X> {
+        if filter.len() != self.len() {
+            panic!("bad filter size self={:?} filter={:?}",self.len(),filter.len());
+        }
+        let filters = self.ranges().map(|range| filter.slice(range).unwrap()).collect::<Vec<_>>();
+        let chunks = self.chunks.iter().zip(filters.iter()).map(|(chunk,filter)| chunk.filter(filter)).collect();
+        EachOrEveryChunked::from_chunks(chunks,filters.iter().map(|filter| filter.count()))
+    }
+
+    /* None if other is of the wrong length */
+    pub fn zip<W,F,Y>(&self, other: &EachOrEvery<Y>, cb: F) -> Option<EachOrEveryChunked<W>> where F: Fn(&X,&Y) -> W {
+        if !other.compatible(self.len()) { return None; }
+        let chunks = self.chunks.iter().zip(self.ranges()).map(|(chunk,range)| {
+            chunk.zip(&other.slice(range).unwrap(),&cb)
+        }).collect();
+        Some(EachOrEveryChunked { chunks, ends: self.ends.clone() })
+    }
+
+    /* Chunks of the result break wherever either input does. None if lengths differ. */
+    pub fn zip_chunked<W,F,Y>(&self, other: &EachOrEveryChunked<Y>, cb: F) -> Option<EachOrEveryChunked<W>> where F: Fn(&X,&Y) -> W {
+        if self.len() != other.len() { return None; }
+        let mut ends = self.ends.iter().chain(other.ends.iter()).copied().collect::<Vec<_>>();
+        ends.sort_unstable();
+        ends.dedup();
+        ends.retain(|end| *end > 0);
+        let mut chunks = vec![];
+        let mut start = 0;
+        for end in ends.iter() {
+            chunks.push(self.within_chunk(start..*end).zip(&other.within_chunk(start..*end),&cb));
+            start = *end;
+        }
+        Some(EachOrEveryChunked { chunks, ends })
+    }
+}
+
+impl<X: Clone> EachOrEveryChunked<X> {
+    /* Copy into a single EoE, merging dictionaries with cb as in EachOrEvery::concat */
+    pub fn flatten<F,Z>(&self, cb: F) -> EachOrEvery<X> where F: Fn(&X) -> Z, Z: Eq+Hash {
+        let parts = self.chunks.iter().zip(self.ranges()).map(|(chunk,range)| (chunk,range.len())).collect::<Vec<_>>();
+        EachOrEvery::concat(&parts,cb).unwrap()
     }
 }
 
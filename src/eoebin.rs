@@ -0,0 +1,130 @@
+use super::{EachOrEvery, EachOrEveryProgression};
+
+/* How the values falling in a bin are combined. Mean is weighted by overlap for ranged data. */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum EachOrEveryBinning {
+    Min,
+    Max,
+    Mean
+}
+
+#[derive(Clone)]
+struct BinTotal {
+    min: f64,
+    max: f64,
+    sum: f64,
+    weight: f64
+}
+
+impl BinTotal {
+    fn new() -> BinTotal {
+        BinTotal { min: f64::INFINITY, max: f64::NEG_INFINITY, sum: 0., weight: 0. }
+    }
+
+    fn add(&mut self, value: f64, weight: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value*weight;
+        self.weight += weight;
+    }
+}
+
+/* Reduces a signal over the region from..to to a fixed number of equal bins, for zoomed-out
+ * display. Without ends, each position is a point in the bin containing it. With ends, each
+ * is a range [start,end) contributing to every bin it overlaps, weighted by the overlap.
+ * Points and ranges outside the region, empty, or involving NaN are ignored.
+ */
+pub struct EachOrEveryBinBuilder {
+    len: usize,
+    from: f64,
+    to: f64,
+    bins: usize,
+    start: EachOrEvery<f64>,
+    end: Option<EachOrEvery<f64>>
+}
+
+impl EachOrEveryBinBuilder {
+    /* None if len is incompatible with start */
+    pub fn new(start: &EachOrEvery<f64>, len: usize, from: f64, to: f64, bins: usize) -> Option<EachOrEveryBinBuilder> {
+        if !start.compatible(len) { return None; }
+        Some(EachOrEveryBinBuilder { len, from, to, bins, start: start.clone(), end: None })
+    }
+
+    pub fn end(&mut self, end: &EachOrEvery<f64>) -> &mut Self {
+        self.end = Some(end.clone());
+        self
+    }
+
+    fn step(&self) -> f64 { (self.to-self.from)/(self.bins as f64) }
+
+    /* The start of each bin */
+    pub fn starts(&self) -> EachOrEveryProgression<f64> {
+        EachOrEveryProgression::new(self.from,self.step(),self.bins)
+    }
+
+    fn bin_of(&self, pos: f64) -> usize {
+        (((pos-self.from)/self.step()) as usize).min(self.bins-1)
+    }
+
+    /* Totals for each bin of the given value at each position. Values are walked via their
+     * index, so constant columns are not expanded. None if values or end are incompatible.
+     */
+    fn totals(&self, values: &EachOrEvery<f64>) -> Option<Vec<BinTotal>> {
+        if !values.compatible(self.len) { return None; }
+        if let Some(end) = &self.end {
+            if !end.compatible(self.len) { return None; }
+        }
+        let mut out = vec![BinTotal::new();self.bins];
+        if self.bins == 0 || self.to <= self.from { return Some(out); }
+        let step = self.step();
+        let mut ends = self.end.as_ref().map(|end| end.index_iter(self.len).map(move |i| end.data[i]));
+        let starts = self.start.index_iter(self.len).map(|i| self.start.data[i]);
+        let values = values.index_iter(self.len).map(|i| values.data[i]);
+        for (start,value) in starts.zip(values) {
+            let end = ends.as_mut().map(|ends| ends.next().unwrap());
+            if start.is_nan() || value.is_nan() || end.map(|end| end.is_nan()).unwrap_or(false) { continue; }
+            match end {
+                None => {
+                    if start >= self.from && start < self.to {
+                        out[self.bin_of(start)].add(value,1.);
+                    }
+                },
+                Some(end) => {
+                    let (start,end) = (start.max(self.from),end.min(self.to));
+                    if end <= start { continue; }
+                    let (first,last) = (self.bin_of(start),self.bin_of(end));
+                    for (bin,total) in out.iter_mut().enumerate().take(last+1).skip(first) {
+                        let bin_start = self.from + (bin as f64)*step;
+                        let overlap = end.min(bin_start+step) - start.max(bin_start);
+                        if overlap > 0. {
+                            total.add(value,overlap);
+                        }
+                    }
+                }
+            }
+        }
+        Some(out)
+    }
+
+    /* NaN for bins with no data. None if values or end are incompatible with the group. */
+    pub fn bin(&self, values: &EachOrEvery<f64>, binning: EachOrEveryBinning) -> Option<EachOrEvery<f64>> {
+        Some(EachOrEvery::each(self.totals(values)?.iter().map(|total| {
+            if total.weight == 0. { return f64::NAN; }
+            match binning {
+                EachOrEveryBinning::Min => total.min,
+                EachOrEveryBinning::Max => total.max,
+                EachOrEveryBinning::Mean => total.sum/total.weight
+            }
+        }).collect()))
+    }
+
+    /* With ends, the mean depth of features over each bin (1 being exactly covered once).
+     * Without, the number of points in each bin. None if end is incompatible with the group.
+     */
+    pub fn coverage(&self) -> Option<EachOrEvery<f64>> {
+        let totals = self.totals(&EachOrEvery::every(1.))?;
+        let scale = if self.end.is_some() { self.step() } else { 1. };
+        Some(EachOrEvery::each(totals.iter().map(|total| total.weight/scale).collect()))
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+use super::{EachOrEvery, EachOrEveryGroupCompatible, eachorevery::{EachOrEveryIndex, SharedIndex}};
+
+/* A file is
+ *   header: "EOEC", version byte, varint length of the rest of the file
+ *   body: varint group length (plus one, zero if no column is finite), varint column count,
+ *         then for each column:
+ *           varint name length, name, type byte, layout byte,
+ *           varint data length, data, index (depending on layout, see below)
+ * Numbers are little-endian f64s, strings a varint length followed by UTF-8, booleans are
+ * packed eight to a byte. Indexes are varints: a count followed by the positions for
+ * indexed data, a count followed by (data index, run length) pairs for runs, and just the
+ * length for repeats.
+ */
+
+const MAGIC : &[u8] = b"EOEC";
+const VERSION : u8 = 1;
+
+const TYPE_NUMBER : u8 = 0;
+const TYPE_STRING : u8 = 1;
+const TYPE_BOOLEAN : u8 = 2;
+
+const LAYOUT_EACH : u8 = 0;
+const LAYOUT_INDEXED : u8 = 1;
+const LAYOUT_RUNS : u8 = 2;
+const LAYOUT_REPEAT : u8 = 3;
+const LAYOUT_EVERY : u8 = 4;
+
+fn binary_error(msg: &str) -> String { format!("bad eoe binary: {}",msg) }
+
+#[derive(Clone)]
+#[cfg_attr(debug_assertions,derive(Debug))]
+pub enum EachOrEveryColumn {
+    Number(EachOrEvery<f64>),
+    String(EachOrEvery<String>),
+    Boolean(EachOrEvery<bool>)
+}
+
+impl EachOrEveryColumn {
+    fn add_to(&self, compat: &mut EachOrEveryGroupCompatible) {
+        match self {
+            EachOrEveryColumn::Number(x) => { compat.add(x); },
+            EachOrEveryColumn::String(x) => { compat.add(x); },
+            EachOrEveryColumn::Boolean(x) => { compat.add(x); }
+        }
+    }
+}
+
+fn check_group(columns: &[(String,EachOrEveryColumn)]) -> Result<Option<usize>,String> {
+    let mut compat = EachOrEveryGroupCompatible::new(None);
+    for (_,column) in columns.iter() {
+        column.add_to(&mut compat);
+    }
+    if !compat.compatible() { return Err(binary_error("incompatible column lengths")); }
+    Ok(compat.len())
+}
+
+struct BinaryWriter(Vec<u8>);
+
+impl BinaryWriter {
+    fn varint(&mut self, mut value: usize) {
+        while value >= 0x80 {
+            self.0.push((value as u8 & 0x7F) | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.varint(value.len());
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn index(&mut self, index: &EachOrEveryIndex) {
+        match index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => { self.0.push(LAYOUT_EACH); },
+            EachOrEveryIndex::Indexed(_) => { self.0.push(LAYOUT_INDEXED); },
+            EachOrEveryIndex::RunLength(_) => { self.0.push(LAYOUT_RUNS); },
+            EachOrEveryIndex::Repeat(_) => { self.0.push(LAYOUT_REPEAT); },
+            EachOrEveryIndex::Every => { self.0.push(LAYOUT_EVERY); }
+        }
+    }
+
+    fn index_data(&mut self, index: &EachOrEveryIndex) {
+        match index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) | EachOrEveryIndex::Every => {},
+            EachOrEveryIndex::Indexed(index) => {
+                self.varint(index.len());
+                for value in index.iter() { self.varint(*value); }
+            },
+            EachOrEveryIndex::RunLength(runs) => {
+                self.varint(runs.len());
+                let mut start = 0;
+                for (value,end) in runs.iter() {
+                    self.varint(*value);
+                    self.varint(*end-start);
+                    start = *end;
+                }
+            },
+            EachOrEveryIndex::Repeat(len) => { self.varint(*len); }
+        }
+    }
+
+    /* Only data in use is written, so filtered views don't carry their whole dictionary */
+    fn column<X,F>(&mut self, kind: u8, column: &EachOrEvery<X>, cb: F) where F: Fn(&mut BinaryWriter,&[&X]) {
+        let (index,data) = column.referenced();
+        self.0.push(kind);
+        self.index(&index);
+        self.varint(data.len());
+        cb(self,&data);
+        self.index_data(&index);
+    }
+}
+
+pub fn eoe_to_binary(columns: &[(String,EachOrEveryColumn)]) -> Result<Vec<u8>,String> {
+    let len = check_group(columns)?;
+    let mut body = BinaryWriter(vec![]);
+    body.varint(len.map(|x| x+1).unwrap_or(0));
+    body.varint(columns.len());
+    for (name,column) in columns.iter() {
+        body.string(name);
+        match column {
+            EachOrEveryColumn::Number(x) => {
+                body.column(TYPE_NUMBER,x,|out,data| {
+                    for value in data { out.0.extend_from_slice(&value.to_le_bytes()); }
+                });
+            },
+            EachOrEveryColumn::String(x) => {
+                body.column(TYPE_STRING,x,|out,data| {
+                    for value in data { out.string(value); }
+                });
+            },
+            EachOrEveryColumn::Boolean(x) => {
+                body.column(TYPE_BOOLEAN,x,|out,data| {
+                    for chunk in data.chunks(8) {
+                        out.0.push(chunk.iter().enumerate().fold(0,|acc,(i,b)| acc | ((**b as u8) << i)));
+                    }
+                });
+            }
+        }
+    }
+    let mut out = BinaryWriter(MAGIC.to_vec());
+    out.0.push(VERSION);
+    out.varint(body.0.len());
+    out.0.extend_from_slice(&body.0);
+    Ok(out.0)
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> BinaryReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8],String> {
+        if self.data.len() - self.pos < len { return Err(binary_error("truncated")); }
+        let out = &self.data[self.pos..(self.pos+len)];
+        self.pos += len;
+        Ok(out)
+    }
+
+    fn byte(&mut self) -> Result<u8,String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<usize,String> {
+        let mut out = 0_usize;
+        let mut shift = 0;
+        loop {
+            if shift >= usize::BITS { return Err(binary_error("varint too long")); }
+            let byte = self.byte()?;
+            out |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 { return Ok(out); }
+            shift += 7;
+        }
+    }
+
+    /* counts come from the file, so can't be trusted for preallocation beyond what remains.
+     * Only for things taking at least a byte each.
+     */
+    fn count(&mut self) -> Result<usize,String> {
+        let count = self.varint()?;
+        if count > self.data.len() - self.pos { return Err(binary_error("bad count")); }
+        Ok(count)
+    }
+
+    fn string(&mut self) -> Result<String,String> {
+        let len = self.varint()?;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| binary_error("bad string"))
+    }
+
+    fn index(&mut self, layout: u8, data_len: usize) -> Result<EachOrEveryIndex,String> {
+        Ok(match layout {
+            LAYOUT_EACH => EachOrEveryIndex::Unindexed,
+            LAYOUT_INDEXED => {
+                let len = self.count()?;
+                let mut index = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let value = self.varint()?;
+                    if value >= data_len { return Err(binary_error("index out of range")); }
+                    index.push(value);
+                }
+                EachOrEveryIndex::Indexed(SharedIndex::new(index))
+            },
+            LAYOUT_RUNS => {
+                let len = self.count()?;
+                let mut runs = Vec::with_capacity(len);
+                let mut end = 0_usize;
+                for _ in 0..len {
+                    let value = self.varint()?;
+                    let run = self.varint()?;
+                    if value >= data_len { return Err(binary_error("index out of range")); }
+                    if run == 0 { return Err(binary_error("empty run")); }
+                    end = end.checked_add(run).ok_or_else(|| binary_error("run too long"))?;
+                    runs.push((value,end));
+                }
+                EachOrEveryIndex::RunLength(Arc::new(runs))
+            },
+            LAYOUT_REPEAT|LAYOUT_EVERY => {
+                if data_len != 1 { return Err(binary_error("expected single value")); }
+                if layout == LAYOUT_REPEAT {
+                    EachOrEveryIndex::Repeat(self.varint()?)
+                } else {
+                    EachOrEveryIndex::Every
+                }
+            },
+            _ => { return Err(binary_error("unknown layout")); }
+        })
+    }
+
+    fn column<X,F>(&mut self, layout: u8, mut cb: F) -> Result<EachOrEvery<X>,String> where F: FnMut(&mut Self,usize) -> Result<Vec<X>,String> {
+        let len = self.varint()?;
+        let data = cb(self,len)?;
+        let index = self.index(layout,data.len())?;
+        Ok(EachOrEvery { index, data: Arc::new(data) })
+    }
+}
+
+pub fn eoe_from_binary(data: &[u8]) -> Result<Vec<(String,EachOrEveryColumn)>,String> {
+    let mut reader = BinaryReader { data, pos: 0 };
+    if reader.bytes(MAGIC.len())? != MAGIC { return Err(binary_error("bad magic")); }
+    if reader.byte()? != VERSION { return Err(binary_error("unsupported version")); }
+    let body_len = reader.varint()?;
+    if body_len != data.len() - reader.pos { return Err(binary_error("bad length")); }
+    let group_len = reader.varint()?.checked_sub(1);
+    let num_columns = reader.count()?;
+    let mut out = vec![];
+    for _ in 0..num_columns {
+        let name = reader.string()?;
+        let kind = reader.byte()?;
+        let layout = reader.byte()?;
+        let column = match kind {
+            TYPE_NUMBER => EachOrEveryColumn::Number(reader.column(layout,|reader,len| {
+                (0..len).map(|_| {
+                    let mut bytes = [0;8];
+                    bytes.copy_from_slice(reader.bytes(8)?);
+                    Ok(f64::from_le_bytes(bytes))
+                }).collect()
+            })?),
+            TYPE_STRING => EachOrEveryColumn::String(reader.column(layout,|reader,len| {
+                (0..len).map(|_| reader.string()).collect()
+            })?),
+            TYPE_BOOLEAN => EachOrEveryColumn::Boolean(reader.column(layout,|reader,len| {
+                let bytes = reader.bytes(len.div_ceil(8))?;
+                Ok((0..len).map(|i| bytes[i/8] & (1 << (i%8)) != 0).collect())
+            })?),
+            _ => { return Err(binary_error("unknown type")); }
+        };
+        out.push((name,column));
+    }
+    if reader.pos != data.len() { return Err(binary_error("trailing data")); }
+    if check_group(&out)? != group_len { return Err(binary_error("group length mismatch")); }
+    Ok(out)
+}
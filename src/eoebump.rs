@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+use super::{EachOrEvery, EachOrEveryFilter, EachOrEveryFilterBuilder};
+
+/* Intervals placed in a row: (start, end plus padding), sorted and disjoint */
+struct BumpRow(Vec<(f64,f64)>);
+
+impl BumpRow {
+    /* Adds the interval if it fits, returning whether it did */
+    fn add(&mut self, start: f64, end: f64) -> bool {
+        let at = self.0.partition_point(|(other_start,_)| *other_start < end);
+        if at > 0 && self.0[at-1].1 > start { return false; }
+        self.0.insert(at,(start,end));
+        true
+    }
+}
+
+/* Greedy assignment of the intervals of a group to rows so that no two in a row overlap.
+ * Features are placed in order of decreasing priority (if given), then of start, each into
+ * the lowest row with room. Padding is the minimum gap after the end of each feature.
+ */
+pub struct EachOrEveryBumpBuilder {
+    len: usize,
+    start: EachOrEvery<f64>,
+    end: EachOrEvery<f64>,
+    priority: Option<EachOrEvery<f64>>,
+    padding: f64,
+    max_rows: Option<usize>
+}
+
+impl EachOrEveryBumpBuilder {
+    /* None if len is incompatible with either column */
+    pub fn new(start: &EachOrEvery<f64>, end: &EachOrEvery<f64>, len: usize) -> Option<EachOrEveryBumpBuilder> {
+        if !start.compatible(len) || !end.compatible(len) { return None; }
+        Some(EachOrEveryBumpBuilder {
+            len,
+            start: start.clone(),
+            end: end.clone(),
+            priority: None,
+            padding: 0.,
+            max_rows: None
+        })
+    }
+
+    pub fn priority(&mut self, priority: &EachOrEvery<f64>) -> &mut Self {
+        self.priority = Some(priority.clone());
+        self
+    }
+
+    pub fn padding(&mut self, padding: f64) -> &mut Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn max_rows(&mut self, max_rows: usize) -> &mut Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    /* The row of each feature which fits, aligned with the group filtered by the returned
+     * filter. Features which don't fit within max_rows, or involve NaN, are filtered out.
+     * None if the priority is incompatible with the group.
+     */
+    pub fn bump(&self) -> Option<(EachOrEvery<usize>,EachOrEveryFilter)> {
+        let priorities = match &self.priority {
+            Some(priority) => Some(priority.map_positions(self.len,|x| *x)?),
+            None => None
+        };
+        let starts = self.start.iter(self.len).unwrap().cloned().collect::<Vec<_>>();
+        let ends = self.end.iter(self.len).unwrap().cloned().collect::<Vec<_>>();
+        let mut order = (0..self.len).filter(|pos| !starts[*pos].is_nan() && !ends[*pos].is_nan()).collect::<Vec<_>>();
+        order.sort_by(|a,b| {
+            let by_priority = priorities.as_ref()
+                .map(|priorities| priorities[*b].total_cmp(&priorities[*a]))
+                .unwrap_or(Ordering::Equal);
+            by_priority.then_with(|| starts[*a].total_cmp(&starts[*b]))
+        });
+        let mut rows : Vec<BumpRow> = vec![];
+        let mut row_of = vec![None;self.len];
+        for pos in order {
+            let (start,end) = (starts[pos],ends[pos]+self.padding);
+            let row = match rows.iter_mut().position(|row| row.add(start,end)) {
+                Some(row) => row,
+                None => {
+                    if self.max_rows.map(|max| rows.len() >= max).unwrap_or(false) { continue; }
+                    rows.push(BumpRow(vec![(start,end)]));
+                    rows.len()-1
+                }
+            };
+            row_of[pos] = Some(row);
+        }
+        let mut fits = EachOrEveryFilterBuilder::new();
+        let mut out = vec![];
+        for (pos,row) in row_of.iter().enumerate() {
+            if let Some(row) = row {
+                fits.set(pos);
+                out.push(*row);
+            }
+        }
+        Some((EachOrEvery::each(out),fits.make(self.len)))
+    }
+}
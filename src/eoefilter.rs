@@ -1,14 +1,31 @@
-use std::sync::Arc;
-use super::{EachOrEvery, eachorevery::EachOrEveryIndex};
+use std::ops::Range;
+use super::{EachOrEvery, eachorevery::{EachOrEveryIndex, RunLengthBuilder, SharedIndex}};
 
-fn un_rle<F>(input: &[(usize,usize)], cb: F) -> Arc<Vec<usize>> where F: Fn(usize) -> usize {
+fn un_rle<F>(input: &[(usize,usize)], cb: F) -> SharedIndex where F: Fn(usize) -> usize {
     let mut out = vec![];
     for (start,len) in input {
         for i in *start..(*start+*len) {
             out.push(cb(i));
         }
     }
-    Arc::new(out)
+    SharedIndex::new(out)
+}
+
+/* Filter ranges are in order, so we only ever need to move forward through the runs */
+fn filter_runs(input: &[(usize,usize)], runs: &[(usize,usize)]) -> EachOrEveryIndex {
+    let mut out = RunLengthBuilder::new();
+    let mut run = 0;
+    for (start,len) in input {
+        let mut pos = *start;
+        let end = *start+*len;
+        while pos < end {
+            while runs[run].1 <= pos { run += 1; }
+            let stop = end.min(runs[run].1);
+            out.push(runs[run].0,stop-pos);
+            pos = stop;
+        }
+    }
+    out.make()
 }
 
 struct NumIterator<'a> {
@@ -65,6 +82,18 @@ impl EachOrEveryFilterBuilder {
         self.0.push((index,1));
     }
 
+    pub fn set_run(&mut self, index: usize, len: usize) {
+        if len == 0 { return; }
+        self.1 += len;
+        if let Some((last_index,last_len)) = self.0.last_mut() {
+            if *last_index + *last_len == index {
+                *last_len += len;
+                return;
+            }
+        }
+        self.0.push((index,len));
+    }
+
     pub fn make(self, len: usize) -> EachOrEveryFilter {
         if self.0.len() == 0 {
             EachOrEveryFilter::none(len)
@@ -172,6 +201,46 @@ impl EachOrEveryFilter {
     pub fn len(&self) -> usize { self.len }
     pub fn count(&self) -> usize { self.count }
 
+    /* The part of this filter covering range, renumbered to start at zero. */
+    pub fn slice(&self, range: Range<usize>) -> Option<EachOrEveryFilter> {
+        if range.start > range.end || range.end > self.len { return None; }
+        let len = range.end - range.start;
+        Some(match &self.data {
+            EachOrEveryFilterData::All => EachOrEveryFilter::all(len),
+            EachOrEveryFilterData::None => EachOrEveryFilter::none(len),
+            EachOrEveryFilterData::Some(ranges) => {
+                let mut out = EachOrEveryFilterBuilder::new();
+                let first = ranges.partition_point(|(start,len)| start+len <= range.start);
+                for (start,run_len) in ranges[first..].iter().take_while(|(start,_)| *start < range.end) {
+                    let from = (*start).max(range.start);
+                    let to = (start+run_len).min(range.end);
+                    out.set_run(from-range.start,to-from);
+                }
+                out.make(len)
+            }
+        })
+    }
+
+    /* The positions which pass, if they form a single (possibly empty) range */
+    pub(super) fn single_run(&self) -> Option<Range<usize>> {
+        match &self.data {
+            EachOrEveryFilterData::All => Some(0..self.len),
+            EachOrEveryFilterData::None => Some(0..0),
+            EachOrEveryFilterData::Some(ranges) if ranges.len() == 1 => Some(ranges[0].0..(ranges[0].0+ranges[0].1)),
+            EachOrEveryFilterData::Some(_) => None
+        }
+    }
+
+    /* Positions which pass the filter, in increasing order */
+    pub fn positions(&self) -> impl Iterator<Item=usize> + '_ {
+        let (all,ranges) : (usize,&[(usize,usize)]) = match &self.data {
+            EachOrEveryFilterData::All => (self.len,&[]),
+            EachOrEveryFilterData::None => (0,&[]),
+            EachOrEveryFilterData::Some(ranges) => (0,ranges)
+        };
+        (0..all).chain(ranges.iter().flat_map(|(start,len)| *start..(*start+*len)))
+    }
+
     pub fn filter_clone<Z: Clone>(&self, input: &[Z]) -> Vec<Z> {
         if input.len() == 0 { return vec![]; }
         match &self.data {
@@ -199,8 +268,11 @@ impl EachOrEveryFilter {
             EachOrEveryFilterData::Some(filter) => {
                 let index = match &data.index {
                     EachOrEveryIndex::Every => EachOrEveryIndex::Every,
+                    EachOrEveryIndex::Repeat(_) => EachOrEveryIndex::Repeat(self.count),
                     EachOrEveryIndex::Unindexed => EachOrEveryIndex::Indexed(un_rle(&filter,|i| i)),
-                    EachOrEveryIndex::Indexed(index) => EachOrEveryIndex::Indexed(un_rle(&filter,|i| index[i]))
+                    EachOrEveryIndex::Window(start,_) => EachOrEveryIndex::Indexed(un_rle(filter,|i| start+i)),
+                    EachOrEveryIndex::Indexed(index) => EachOrEveryIndex::Indexed(un_rle(&filter,|i| index[i])),
+                    EachOrEveryIndex::RunLength(runs) => filter_runs(filter,runs)
                 };
                 EachOrEvery { index, data: data.data.clone() }        
             }
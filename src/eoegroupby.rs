@@ -0,0 +1,122 @@
+use std::{hash::Hash, sync::Arc};
+use hashbrown::HashMap;
+use super::EachOrEvery;
+
+/* Positions of a group of columns grouped by the value of a key column. Aggregates of other
+ * columns in the group are aligned with keys(): one entry per distinct key, in order of first
+ * appearance. Columns are walked in place, no filtered copies are made. Aggregates are None
+ * if the column is incompatible with the group.
+ */
+pub struct EachOrEveryGroupBy<K> {
+    len: usize,
+    keys: EachOrEvery<K>,
+    counts: Vec<usize>,
+    /* None when every position is in group 0 */
+    group_of: Option<Vec<usize>>
+}
+
+impl<K: Clone+Hash+Eq> EachOrEveryGroupBy<K> {
+    /* The key callback is called only once per data item in use. None if len is incompatible. */
+    pub fn new<X,F>(key: &EachOrEvery<X>, len: usize, cb: F) -> Option<EachOrEveryGroupBy<K>> where F: Fn(&X) -> K {
+        if !key.compatible(len) { return None; }
+        if key.index.is_constant() {
+            let keys = if len > 0 { vec![cb(&key.data[0])] } else { vec![] };
+            let counts = if len > 0 { vec![len] } else { vec![] };
+            return Some(EachOrEveryGroupBy { len, keys: EachOrEvery::each(keys), counts, group_of: None });
+        }
+        let mut data_group = key.data_cache();
+        let mut key_to_group = HashMap::new();
+        let mut keys = vec![];
+        let mut counts = vec![];
+        let mut group_of = Vec::with_capacity(len);
+        for index in key.index_iter(len) {
+            let group = *data_group.get_or_insert_with(index,|| {
+                let value = cb(&key.data[index]);
+                *key_to_group.entry(value.clone()).or_insert_with(|| {
+                    keys.push(value);
+                    counts.push(0);
+                    keys.len()-1
+                })
+            });
+            counts[group] += 1;
+            group_of.push(group);
+        }
+        Some(EachOrEveryGroupBy { len, keys: EachOrEvery::each(keys), counts, group_of: Some(group_of) })
+    }
+}
+
+impl<K> EachOrEveryGroupBy<K> {
+    pub fn len(&self) -> usize { self.counts.len() }
+    pub fn is_empty(&self) -> bool { self.counts.is_empty() }
+    pub fn keys(&self) -> EachOrEvery<K> { self.keys.clone() }
+    pub fn count(&self) -> EachOrEvery<usize> { EachOrEvery::each(self.counts.clone()) }
+
+    /* Values must be compatible */
+    fn each_value<'a,X,F>(&self, values: &'a EachOrEvery<X>, mut cb: F) where F: FnMut(usize,usize,&'a X) {
+        for (pos,index) in values.index_iter(self.len).enumerate() {
+            let group = self.group_of.as_ref().map(|group_of| group_of[pos]).unwrap_or(0);
+            cb(group,pos,&values.data[index]);
+        }
+    }
+
+    /* Per group, the value at the position for which better(candidate,best) last held.
+     * Shares data with the input.
+     */
+    fn select<X,F>(&self, values: &EachOrEvery<X>, better: F) -> Option<EachOrEvery<X>> where F: Fn(&X,&X) -> bool {
+        if !values.compatible(self.len) { return None; }
+        if values.index.is_constant() {
+            return Some(values.reindex(&Arc::new(vec![0;self.len()])));
+        }
+        let mut best : Vec<Option<(usize,&X)>> = vec![None;self.len()];
+        self.each_value(values,|group,pos,value| {
+            match best[group] {
+                Some((_,old)) if !better(value,old) => {},
+                _ => { best[group] = Some((pos,value)); }
+            }
+        });
+        Some(values.reindex(&Arc::new(best.iter().map(|x| x.unwrap().0).collect())))
+    }
+
+    pub fn first<X>(&self, values: &EachOrEvery<X>) -> Option<EachOrEvery<X>> {
+        self.select(values,|_,_| false)
+    }
+
+    pub fn last<X>(&self, values: &EachOrEvery<X>) -> Option<EachOrEvery<X>> {
+        self.select(values,|_,_| true)
+    }
+
+    /* Ties go to the first. Incomparable values (eg NaN) are only chosen if nothing else is present. */
+    pub fn min<X: PartialOrd>(&self, values: &EachOrEvery<X>) -> Option<EachOrEvery<X>> {
+        self.select(values,|value,best| value < best || best.partial_cmp(best).is_none())
+    }
+
+    pub fn max<X: PartialOrd>(&self, values: &EachOrEvery<X>) -> Option<EachOrEvery<X>> {
+        self.select(values,|value,best| value > best || best.partial_cmp(best).is_none())
+    }
+
+    /* Folds the values of each group, in position order, into a copy of init */
+    pub fn fold<X,Y: Clone,F>(&self, values: &EachOrEvery<X>, init: Y, f: F) -> Option<EachOrEvery<Y>> where F: Fn(&mut Y,&X) {
+        if !values.compatible(self.len) { return None; }
+        let mut out = vec![init;self.len()];
+        self.each_value(values,|group,_,value| f(&mut out[group],value));
+        Some(EachOrEvery::each(out))
+    }
+
+    pub fn sum(&self, values: &EachOrEvery<f64>) -> Option<EachOrEvery<f64>> {
+        if !values.compatible(self.len) { return None; }
+        if values.index.is_constant() {
+            let value = values.data[0];
+            return Some(EachOrEvery::each(self.counts.iter().map(|count| value*(*count as f64)).collect()));
+        }
+        self.fold(values,0.,|total,value| *total += *value)
+    }
+
+    pub fn mean(&self, values: &EachOrEvery<f64>) -> Option<EachOrEvery<f64>> {
+        if !values.compatible(self.len) { return None; }
+        if values.index.is_constant() {
+            return Some(values.reindex(&Arc::new(vec![0;self.len()])));
+        }
+        let sums = self.sum(values)?;
+        Some(EachOrEvery::each(sums.data.iter().zip(self.counts.iter()).map(|(sum,count)| sum/(*count as f64)).collect()))
+    }
+}
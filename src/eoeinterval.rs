@@ -0,0 +1,185 @@
+use super::{EachOrEvery, EachOrEveryFilter, EachOrEveryFilterBuilder};
+
+/* How the ends of intervals are treated when testing for overlap. HalfOpen intervals [start,end)
+ * which merely abut do not overlap, Closed intervals [start,end] do.
+ */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum EachOrEveryOverlap {
+    HalfOpen,
+    Closed
+}
+
+impl EachOrEveryOverlap {
+    /* Could an interval starting at start overlap a window ending at window_end? */
+    pub(super) fn starts_before(&self, start: f64, window_end: f64) -> bool {
+        match self {
+            EachOrEveryOverlap::HalfOpen => start < window_end,
+            EachOrEveryOverlap::Closed => start <= window_end
+        }
+    }
+
+    /* Could an interval ending at end overlap a window starting at window_start? */
+    pub(super) fn ends_after(&self, end: f64, window_start: f64) -> bool {
+        match self {
+            EachOrEveryOverlap::HalfOpen => end > window_start,
+            EachOrEveryOverlap::Closed => end >= window_start
+        }
+    }
+}
+
+/* Positions whose interval overlaps the window. The start and end tests are made separately,
+ * each once per data item in use, so constant columns are never expanded. Intervals involving
+ * NaN never overlap. None if len is incompatible with either column.
+ */
+pub fn eoe_overlap_filter(start: &EachOrEvery<f64>, end: &EachOrEvery<f64>, len: usize, window_start: f64, window_end: f64, mode: EachOrEveryOverlap) -> Option<EachOrEveryFilter> {
+    if !start.compatible(len) || !end.compatible(len) { return None; }
+    let starts = start.make_filter(len,|start| mode.starts_before(*start,window_end));
+    if starts.count() == 0 { return Some(starts); }
+    Some(starts.and(&end.make_filter(len,|end| mode.ends_after(*end,window_start))))
+}
+
+/* An index over the intervals of a group, for repeated queries. Intervals are sorted by start
+ * and form an implicit balanced tree (the root of each range is its midpoint) where each
+ * entry also records the greatest end in its subtree, so subtrees ending too early are skipped.
+ * Queries are O(k log n) in the worst case, for k results. Intervals involving NaN are left out.
+ */
+pub struct EachOrEveryIntervalIndex {
+    len: usize,
+    mode: EachOrEveryOverlap,
+    starts: Vec<f64>,
+    ends: Vec<f64>,
+    positions: Vec<usize>,
+    max_ends: Vec<f64>
+}
+
+impl EachOrEveryIntervalIndex {
+    /* None if len is incompatible with either column */
+    pub fn new(start: &EachOrEvery<f64>, end: &EachOrEvery<f64>, len: usize, mode: EachOrEveryOverlap) -> Option<EachOrEveryIntervalIndex> {
+        let mut intervals = start.iter(len)?.zip(end.iter(len)?).enumerate()
+            .filter(|(_,(start,end))| !start.is_nan() && !end.is_nan())
+            .map(|(pos,(start,end))| (*start,*end,pos))
+            .collect::<Vec<_>>();
+        intervals.sort_by(|a,b| a.0.total_cmp(&b.0));
+        let mut out = EachOrEveryIntervalIndex {
+            len, mode,
+            starts: intervals.iter().map(|x| x.0).collect(),
+            ends: intervals.iter().map(|x| x.1).collect(),
+            positions: intervals.iter().map(|x| x.2).collect(),
+            max_ends: vec![0.;intervals.len()]
+        };
+        out.build(0,intervals.len());
+        Some(out)
+    }
+
+    /* Fills in max_ends for the subtree over lo..hi, returning its greatest end */
+    fn build(&mut self, lo: usize, hi: usize) -> f64 {
+        if lo >= hi { return f64::NEG_INFINITY; }
+        let mid = (lo+hi)/2;
+        let max_end = self.ends[mid].max(self.build(lo,mid)).max(self.build(mid+1,hi));
+        self.max_ends[mid] = max_end;
+        max_end
+    }
+
+    /* Entries within lo..hi passing both tests. start_ok must only fail beyond some start,
+     * end_ok only below some end, so that whole subtrees can be skipped.
+     */
+    fn search<F,G>(&self, lo: usize, hi: usize, start_ok: &F, end_ok: &G, out: &mut Vec<usize>) where F: Fn(f64) -> bool, G: Fn(f64) -> bool {
+        if lo >= hi { return; }
+        let mid = (lo+hi)/2;
+        if !end_ok(self.max_ends[mid]) { return; }
+        self.search(lo,mid,start_ok,end_ok,out);
+        if start_ok(self.starts[mid]) {
+            if end_ok(self.ends[mid]) {
+                out.push(self.positions[mid]);
+            }
+            self.search(mid+1,hi,start_ok,end_ok,out);
+        }
+    }
+
+    fn filter<F,G>(&self, start_ok: F, end_ok: G) -> EachOrEveryFilter where F: Fn(f64) -> bool, G: Fn(f64) -> bool {
+        let mut positions = vec![];
+        self.search(0,self.starts.len(),&start_ok,&end_ok,&mut positions);
+        positions.sort_unstable();
+        let mut out = EachOrEveryFilterBuilder::new();
+        for pos in positions {
+            out.set(pos);
+        }
+        out.make(self.len)
+    }
+
+    /* As eoe_overlap_filter */
+    pub fn overlapping(&self, window_start: f64, window_end: f64) -> EachOrEveryFilter {
+        let mode = self.mode;
+        self.filter(|start| mode.starts_before(start,window_end),|end| mode.ends_after(end,window_start))
+    }
+
+    /* Positions whose interval contains point. The start is always included, the end only if Closed. */
+    pub fn containing(&self, point: f64) -> EachOrEveryFilter {
+        let mode = self.mode;
+        self.filter(|start| start <= point,|end| mode.ends_after(end,point))
+    }
+
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+/* The intervals of a group merged into disjoint blocks, in order, with the rows making up
+ * each block. Intervals merge if they overlap under mode, so Closed also merges intervals
+ * which only touch. Intervals involving NaN are left out.
+ */
+pub struct EachOrEveryIntervalUnion {
+    len: usize,
+    starts: Vec<f64>,
+    ends: Vec<f64>,
+    rows: Vec<Vec<usize>>
+}
+
+impl EachOrEveryIntervalUnion {
+    /* None if len is incompatible with either column */
+    pub fn new(start: &EachOrEvery<f64>, end: &EachOrEvery<f64>, len: usize, mode: EachOrEveryOverlap) -> Option<EachOrEveryIntervalUnion> {
+        let mut intervals = start.iter(len)?.zip(end.iter(len)?).enumerate()
+            .filter(|(_,(start,end))| !start.is_nan() && !end.is_nan())
+            .map(|(pos,(start,end))| (*start,*end,pos))
+            .collect::<Vec<_>>();
+        intervals.sort_by(|a,b| a.0.total_cmp(&b.0));
+        let mut out = EachOrEveryIntervalUnion { len, starts: vec![], ends: vec![], rows: vec![] };
+        for (start,end,pos) in intervals {
+            match out.ends.last_mut() {
+                Some(block_end) if mode.starts_before(start,*block_end) => {
+                    *block_end = block_end.max(end);
+                    out.rows.last_mut().unwrap().push(pos);
+                },
+                _ => {
+                    out.starts.push(start);
+                    out.ends.push(end);
+                    out.rows.push(vec![pos]);
+                }
+            }
+        }
+        for rows in out.rows.iter_mut() {
+            rows.sort_unstable();
+        }
+        Some(out)
+    }
+
+    /* Number of blocks */
+    pub fn len(&self) -> usize { self.starts.len() }
+    pub fn is_empty(&self) -> bool { self.starts.is_empty() }
+    pub fn starts(&self) -> EachOrEvery<f64> { EachOrEvery::each(self.starts.clone()) }
+    pub fn ends(&self) -> EachOrEvery<f64> { EachOrEvery::each(self.ends.clone()) }
+
+    /* Rows in the block, in increasing order, suitable for gather */
+    pub fn rows(&self, block: usize) -> Option<&[usize]> {
+        self.rows.get(block).map(|rows| rows.as_slice())
+    }
+
+    /* Rows in the block as a filter over the group */
+    pub fn filter(&self, block: usize) -> Option<EachOrEveryFilter> {
+        let mut out = EachOrEveryFilterBuilder::new();
+        for pos in self.rows.get(block)? {
+            out.set(*pos);
+        }
+        Some(out.make(self.len))
+    }
+}
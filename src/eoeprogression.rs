@@ -0,0 +1,120 @@
+use std::ops::{Add, Mul};
+use super::{EachOrEvery, EachOrEveryFilter};
+
+/* Numbers which can appear in a progression. from_position converts a position into
+ * a multiplier for the step.
+ */
+pub trait EachOrEveryNumber: Copy + Add<Output=Self> + Mul<Output=Self> {
+    fn from_position(pos: usize) -> Self;
+}
+
+macro_rules! eoe_number {
+    ($($type:ty),*) => {
+        $(
+            impl EachOrEveryNumber for $type {
+                fn from_position(pos: usize) -> $type { pos as $type }
+            }
+        )*
+    };
+}
+
+eoe_number!(f64,f32,i64,i32,isize,u64,u32,usize);
+
+/* A column of len values, start, start+step, start+2*step, ... which is never stored.
+ * Operations which can't be expressed as another progression return an EachOrEvery.
+ */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone,PartialEq)]
+pub struct EachOrEveryProgression<X> {
+    start: X,
+    step: X,
+    len: usize
+}
+
+impl<X: EachOrEveryNumber> EachOrEveryProgression<X> {
+    pub fn new(start: X, step: X, len: usize) -> EachOrEveryProgression<X> {
+        EachOrEveryProgression { start, step, len }
+    }
+
+    pub fn start(&self) -> X { self.start }
+    pub fn step(&self) -> X { self.step }
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    fn value(&self, pos: usize) -> X {
+        self.start + self.step * X::from_position(pos)
+    }
+
+    pub fn get(&self, pos: usize) -> Option<X> {
+        if pos < self.len { Some(self.value(pos)) } else { None }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=X> + '_ {
+        (0..self.len).map(move |pos| self.value(pos))
+    }
+
+    /* x -> x*mul+add is still a progression, so needs no storage */
+    pub fn affine(&self, mul: X, add: X) -> EachOrEveryProgression<X> {
+        EachOrEveryProgression {
+            start: self.start * mul + add,
+            step: self.step * mul,
+            len: self.len
+        }
+    }
+
+    pub fn to_each(&self) -> EachOrEvery<X> {
+        EachOrEvery::each(self.iter().collect())
+    }
+
+    pub fn map<F,Y>(&self, f: F) -> EachOrEvery<Y> where F: FnMut(X) -> Y {
+        EachOrEvery::each(self.iter().map(f).collect())
+    }
+
+    /* Stays a progression if the filter passes a single range, otherwise materialises */
+    pub fn filter(&self, filter: &EachOrEveryFilter) -> EachOrEveryFilteredProgression<X> {
+        if filter.len() != self.len {
+            panic!("bad filter size self={:?} filter={:?}",self.len,filter.len());
+        }
+        match filter.single_run() {
+            Some(range) => {
+                let start = if range.is_empty() { self.start } else { self.value(range.start) };
+                EachOrEveryFilteredProgression::Progression(EachOrEveryProgression::new(start,self.step,range.len()))
+            },
+            None => {
+                EachOrEveryFilteredProgression::Each(EachOrEvery::each(filter.positions().map(|pos| self.value(pos)).collect()))
+            }
+        }
+    }
+
+    /* None if other is incompatible with our length */
+    pub fn zip<W,F,Y>(&self, other: &EachOrEvery<Y>, cb: F) -> Option<EachOrEvery<W>> where F: Fn(&X,&Y) -> W {
+        if !other.compatible(self.len) { return None; }
+        Some(EachOrEvery::each(self.iter().zip(other.iter(self.len)?).map(|(a,b)| cb(&a,b)).collect()))
+    }
+}
+
+/* The result of filtering a progression, which is only materialised if it must be */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone)]
+pub enum EachOrEveryFilteredProgression<X> {
+    Progression(EachOrEveryProgression<X>),
+    Each(EachOrEvery<X>)
+}
+
+impl<X: EachOrEveryNumber> EachOrEveryFilteredProgression<X> {
+    pub fn len(&self) -> usize {
+        match self {
+            EachOrEveryFilteredProgression::Progression(progression) => progression.len(),
+            EachOrEveryFilteredProgression::Each(each) => each.len().unwrap_or(0)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn to_each(&self) -> EachOrEvery<X> {
+        match self {
+            EachOrEveryFilteredProgression::Progression(progression) => progression.to_each(),
+            EachOrEveryFilteredProgression::Each(each) => each.clone()
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{iter, sync::Arc};
+use super::{EachOrEvery, eachorevery::{EachOrEveryIndex, RunLengthBuilder}};
+
+fn run_like(index: &EachOrEveryIndex) -> bool {
+    matches!(index,EachOrEveryIndex::RunLength(_) | EachOrEveryIndex::Repeat(_) | EachOrEveryIndex::Every)
+}
+
+/* Positional operations: unlike map, the output at a position depends on its neighbours. */
+impl<X> EachOrEvery<X> {
+    /* Position i of the output is f of the output at i-1 (init for the first) and the value
+     * at i. None if len is incompatible.
+     */
+    pub fn scan<F,Y: Clone>(&self, len: usize, init: Y, f: F) -> Option<EachOrEvery<Y>> where F: Fn(&Y,&X) -> Y {
+        if !self.compatible(len) { return None; }
+        let mut acc = init;
+        let mut out = Vec::with_capacity(len);
+        for index in self.index_iter(len) {
+            acc = f(&acc,&self.data[index]);
+            out.push(acc.clone());
+        }
+        Some(EachOrEvery::each(out))
+    }
+
+    /* The best value so far at each position. Shares our data, with a run-length index. */
+    fn scan_select<F>(&self, len: usize, better: F) -> Option<EachOrEvery<X>> where F: Fn(&X,&X) -> bool {
+        if !self.compatible(len) { return None; }
+        if self.index.is_constant() { return self.to_each(len); }
+        let mut runs = RunLengthBuilder::new();
+        let mut best : Option<usize> = None;
+        for index in self.index_iter(len) {
+            match best {
+                Some(old) if !better(&self.data[index],&self.data[old]) => {},
+                _ => { best = Some(index); }
+            }
+            runs.push(best.unwrap(),1);
+        }
+        Some(EachOrEvery { index: runs.make(), data: self.data.clone() })
+    }
+
+    /* f of the value at i and the value of next at i+1 (None for the last position). f is called
+     * once for each run of positions with the same pair of data items, so run-length and
+     * constant inputs give run-length output. None if len is incompatible.
+     */
+    pub fn adjacent_zip<F,Y,W>(&self, next: &EachOrEvery<Y>, len: usize, f: F) -> Option<EachOrEvery<W>> where F: Fn(&X,Option<&Y>) -> W {
+        if !self.compatible(len) || !next.compatible(len) { return None; }
+        let nexts = next.index_iter(len).skip(1).map(Some).chain(iter::once(None));
+        let pairs = self.index_iter(len).zip(nexts);
+        let call = |(value,next_value): (usize,Option<usize>)| {
+            f(&self.data[value],next_value.map(|x| &next.data[x]))
+        };
+        if run_like(&self.index) && run_like(&next.index) {
+            let mut data = vec![];
+            let mut runs = RunLengthBuilder::new();
+            let mut prev = None;
+            for pair in pairs {
+                if prev != Some(pair) {
+                    data.push(call(pair));
+                    prev = Some(pair);
+                }
+                runs.push(data.len()-1,1);
+            }
+            Some(EachOrEvery { index: runs.make(), data: Arc::new(data) })
+        } else {
+            Some(EachOrEvery::each(pairs.map(call).collect()))
+        }
+    }
+
+    pub fn adjacent<F,Y>(&self, len: usize, f: F) -> Option<EachOrEvery<Y>> where F: Fn(&X,Option<&X>) -> Y {
+        self.adjacent_zip(self,len,f)
+    }
+}
+
+impl<X: PartialOrd> EachOrEvery<X> {
+    /* Incomparable values (eg NaN) are skipped unless nothing else has been seen. */
+    pub fn cumulative_min(&self, len: usize) -> Option<EachOrEvery<X>> {
+        self.scan_select(len,|value,best| value < best || best.partial_cmp(best).is_none())
+    }
+
+    pub fn cumulative_max(&self, len: usize) -> Option<EachOrEvery<X>> {
+        self.scan_select(len,|value,best| value > best || best.partial_cmp(best).is_none())
+    }
+}
+
+impl EachOrEvery<f64> {
+    pub fn cumulative_sum(&self, len: usize) -> Option<EachOrEvery<f64>> {
+        self.scan(len,0.,|total,value| total+value)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{fmt, marker::PhantomData, sync::Arc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{self, MapAccess, SeqAccess, Visitor}, ser::SerializeStruct};
+use super::{EachOrEvery, eachorevery::{EachOrEveryIndex, SharedIndex}};
+
+/* Serialized as a struct of three fields so that the compact representation survives. Only
+ * data in use is written, so a filtered view doesn't carry its whole dictionary.
+ *   kind: one of "each", "indexed", "runs", "repeat", "every"
+ *   data: the distinct values (for "each" this is simply every value)
+ *   index: empty for "each" and "every", the position to data mapping for "indexed",
+ *          flattened (data index, end) pairs for "runs", and the length for "repeat"
+ */
+const FIELDS : &[&str] = &["kind","data","index"];
+
+fn flatten_runs(runs: &[(usize,usize)]) -> Vec<usize> {
+    runs.iter().flat_map(|(index,end)| [*index,*end]).collect()
+}
+
+impl<X: Serialize> Serialize for EachOrEvery<X> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where S: Serializer {
+        let (index,data) = self.referenced();
+        let (kind,index) = match &index {
+            EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) => ("each",vec![]),
+            EachOrEveryIndex::Indexed(index) => ("indexed",index.to_vec()),
+            EachOrEveryIndex::RunLength(runs) => ("runs",flatten_runs(runs)),
+            EachOrEveryIndex::Repeat(len) => ("repeat",vec![*len]),
+            EachOrEveryIndex::Every => ("every",vec![])
+        };
+        let mut out = serializer.serialize_struct("EachOrEvery",3)?;
+        out.serialize_field("kind",kind)?;
+        out.serialize_field("data",&data)?;
+        out.serialize_field("index",&index)?;
+        out.end()
+    }
+}
+
+fn build<X,E>(kind: &str, data: Vec<X>, index: Vec<usize>) -> Result<EachOrEvery<X>,E> where E: de::Error {
+    let single = |len| {
+        if data.len() != 1 { return Err(E::custom(format!("{} requires exactly one value",kind))); }
+        if index.len() != len { return Err(E::custom(format!("bad index for {}",kind))); }
+        Ok(())
+    };
+    let index = match kind {
+        "each" => {
+            if !index.is_empty() { return Err(E::custom("unexpected index for each")); }
+            EachOrEveryIndex::Unindexed
+        },
+        "indexed" => {
+            if index.iter().any(|x| *x >= data.len()) { return Err(E::custom("index out of range")); }
+            EachOrEveryIndex::Indexed(SharedIndex::new(index))
+        },
+        "runs" => {
+            let pairs = index.chunks_exact(2);
+            if !pairs.remainder().is_empty() { return Err(E::custom("bad index for runs")); }
+            let runs = pairs.map(|x| (x[0],x[1])).collect::<Vec<_>>();
+            let mut prev = 0;
+            for (index,end) in runs.iter() {
+                if *index >= data.len() { return Err(E::custom("index out of range")); }
+                if *end <= prev { return Err(E::custom("run ends must increase")); }
+                prev = *end;
+            }
+            EachOrEveryIndex::RunLength(Arc::new(runs))
+        },
+        "repeat" => {
+            single(1)?;
+            EachOrEveryIndex::Repeat(index[0])
+        },
+        "every" => {
+            single(0)?;
+            EachOrEveryIndex::Every
+        },
+        _ => { return Err(E::unknown_variant(kind,&["each","indexed","runs","repeat","every"])); }
+    };
+    Ok(EachOrEvery { index, data: Arc::new(data) })
+}
+
+struct EachOrEveryVisitor<X>(PhantomData<X>);
+
+impl<'de,X: Deserialize<'de>> Visitor<'de> for EachOrEveryVisitor<X> {
+    type Value = EachOrEvery<X>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an EachOrEvery")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where A: SeqAccess<'de> {
+        let kind : String = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0,&self))?;
+        let data : Vec<X> = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1,&self))?;
+        let index : Vec<usize> = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2,&self))?;
+        build(&kind,data,index)
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+            where M: MapAccess<'de> {
+        let mut kind : Option<String> = None;
+        let mut data : Option<Vec<X>> = None;
+        let mut index : Option<Vec<usize>> = None;
+        while let Some(key) = access.next_key::<String>()? {
+            match key.as_str() {
+                "kind" => { kind = Some(access.next_value()?); },
+                "data" => { data = Some(access.next_value()?); },
+                "index" => { index = Some(access.next_value()?); },
+                _ => { return Err(de::Error::unknown_field(&key,FIELDS)); }
+            }
+        }
+        let kind = kind.ok_or_else(|| de::Error::missing_field("kind"))?;
+        let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
+        build(&kind,data,index.unwrap_or_default())
+    }
+}
+
+impl<'de,X: Deserialize<'de>> Deserialize<'de> for EachOrEvery<X> {
+    fn deserialize<D>(deserializer: D) -> Result<EachOrEvery<X>, D::Error>
+            where D: Deserializer<'de> {
+        deserializer.deserialize_struct("EachOrEvery",FIELDS,EachOrEveryVisitor(PhantomData))
+    }
+}
@@ -0,0 +1,75 @@
+use std::{cmp::Ordering, sync::Arc};
+use super::{EachOrEvery, eachorevery::EachOrEveryIndex};
+
+/* Rank of each position, where equal values share a rank. The comparison function is only
+ * called on the data array, so indexed keys are cheap. Constant keys have no effect on the
+ * order and return None.
+ */
+fn ranks<X,F>(key: &EachOrEvery<X>, len: usize, cmp: F) -> Option<Vec<usize>> where F: Fn(&X,&X) -> Ordering {
+    if let EachOrEveryIndex::Every | EachOrEveryIndex::Repeat(_) = &key.index { return None; }
+    let data = &key.data;
+    let mut order = (0..data.len()).collect::<Vec<_>>();
+    order.sort_by(|a,b| cmp(&data[*a],&data[*b]));
+    let mut data_rank = vec![0;data.len()];
+    let mut rank = 0;
+    for (i,pos) in order.iter().enumerate() {
+        if i > 0 && cmp(&data[order[i-1]],&data[*pos]) != Ordering::Equal {
+            rank += 1;
+        }
+        data_rank[*pos] = rank;
+    }
+    Some(key.index_iter(len).map(|index| data_rank[index]).collect())
+}
+
+pub struct EachOrEverySortBuilder {
+    len: usize,
+    ranks: Vec<Vec<usize>>
+}
+
+impl EachOrEverySortBuilder {
+    pub fn new(len: usize) -> EachOrEverySortBuilder {
+        EachOrEverySortBuilder { len, ranks: vec![] }
+    }
+
+    /* Keys are compared in the order they are added, later keys only breaking ties */
+    pub fn key<X,F>(&mut self, key: &EachOrEvery<X>, cmp: F) -> &mut Self where F: Fn(&X,&X) -> Ordering {
+        if !key.compatible(self.len) {
+            panic!("bad key size self={:?} key={:?}",self.len,key.len());
+        }
+        if let Some(ranks) = ranks(key,self.len,cmp) {
+            self.ranks.push(ranks);
+        }
+        self
+    }
+
+    /* Stable: positions with equal keys keep their original order */
+    pub fn sort(&self) -> EachOrEveryPermutation {
+        let mut order = (0..self.len).collect::<Vec<_>>();
+        order.sort_by(|a,b| {
+            self.ranks.iter()
+                .map(|ranks| ranks[*a].cmp(&ranks[*b]))
+                .find(|x| *x != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        EachOrEveryPermutation(Arc::new(order))
+    }
+}
+
+/* The result of a sort: position i of the output is position self.0[i] of the input. */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone)]
+pub struct EachOrEveryPermutation(Arc<Vec<usize>>);
+
+impl EachOrEveryPermutation {
+    pub fn len(&self) -> usize { self.0.len() }
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+    pub fn positions(&self) -> &[usize] { &self.0 }
+
+    /* Reorders without copying data. Unindexed inputs even share the permutation as their index. */
+    pub fn apply<X>(&self, data: &EachOrEvery<X>) -> EachOrEvery<X> {
+        if !data.compatible(self.len()) {
+            panic!("bad permutation size self={:?} permutation={:?}",data.len(),self.len());
+        }
+        data.reindex(&self.0)
+    }
+}
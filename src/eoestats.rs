@@ -0,0 +1,94 @@
+use super::{EachOrEvery, EachOrEveryFilter, eachorevery::EachOrEveryIndex};
+
+/* Summary statistics of a numeric column. Each data item is visited once, weighted by the
+ * number of positions which use it, so indexed and constant columns are cheap. NaNs are
+ * counted separately and otherwise ignored. Variance is the population variance, and is NaN
+ * if any value is infinite (as on log-scaled tracks), though the other statistics are valid.
+ */
+#[cfg_attr(debug_assertions,derive(Debug))]
+#[derive(Clone)]
+pub struct EachOrEveryStats {
+    count: usize,
+    nan_count: usize,
+    min: f64,
+    max: f64,
+    sum: f64,
+    /* Welford's accumulators, over finite values only */
+    finite_count: usize,
+    finite_mean: f64,
+    m2: f64
+}
+
+impl EachOrEveryStats {
+    fn empty() -> EachOrEveryStats {
+        EachOrEveryStats {
+            count: 0, nan_count: 0,
+            min: f64::INFINITY, max: f64::NEG_INFINITY,
+            sum: 0., finite_count: 0, finite_mean: 0., m2: 0.
+        }
+    }
+
+    /* None if len is incompatible */
+    pub fn new(values: &EachOrEvery<f64>, len: usize) -> Option<EachOrEveryStats> {
+        if !values.compatible(len) { return None; }
+        let mut out = EachOrEveryStats::empty();
+        values.data_usage(len,|value,count| out.add(*value,count));
+        Some(out)
+    }
+
+    /* Only the positions which pass the filter. None if the filter is the wrong size. */
+    pub fn new_filtered(values: &EachOrEvery<f64>, filter: &EachOrEveryFilter) -> Option<EachOrEveryStats> {
+        if !values.compatible(filter.len()) { return None; }
+        if let EachOrEveryIndex::Unindexed | EachOrEveryIndex::Window(_,_) = &values.index {
+            /* no dictionary to weight, so just visit the positions which pass */
+            let data = values.window_data();
+            let mut out = EachOrEveryStats::empty();
+            for pos in filter.positions() {
+                out.add(data[pos],1);
+            }
+            return Some(out);
+        }
+        EachOrEveryStats::new(&values.filter(filter),filter.count())
+    }
+
+    fn add(&mut self, value: f64, weight: usize) {
+        if value.is_nan() {
+            self.nan_count += weight;
+            return;
+        }
+        let weight_f = weight as f64;
+        self.count += weight;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value*weight_f;
+        if !value.is_finite() { return; }
+        /* weighted form of Welford's algorithm */
+        self.finite_count += weight;
+        let delta = value-self.finite_mean;
+        self.finite_mean += delta*weight_f/(self.finite_count as f64);
+        self.m2 += weight_f*delta*(value-self.finite_mean);
+    }
+
+    /* Positions which are not NaN */
+    pub fn count(&self) -> usize { self.count }
+    pub fn nan_count(&self) -> usize { self.nan_count }
+    pub fn sum(&self) -> f64 { self.sum }
+
+    pub fn min(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.min) } else { None }
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.max) } else { None }
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.sum/(self.count as f64)) } else { None }
+    }
+
+    pub fn variance(&self) -> Option<f64> {
+        if self.count == 0 { return None; }
+        if self.finite_count < self.count { return Some(f64::NAN); }
+        Some(self.m2/(self.count as f64))
+    }
+}
@@ -0,0 +1,82 @@
+use std::hash::Hash;
+use hashbrown::HashMap;
+use super::{EachOrEvery, EachOrEveryFilter, EachOrEveryFilterBuilder, EachOrEveryColumn};
+
+/* Combines columns from adjacent tiles where features crossing a boundary appear in more
+ * than one tile. Built from an identity column per tile; a row survives only if no earlier
+ * row (in tile order, then position order) had the same identity. The filters can then be
+ * used to merge any other column of the tiles.
+ */
+pub struct EachOrEveryTileMerge {
+    filters: Vec<EachOrEveryFilter>
+}
+
+impl EachOrEveryTileMerge {
+    /* One (identity column, length) per tile. None if a column has the wrong length. */
+    pub fn new<X,F,K>(tiles: &[(&EachOrEvery<X>,usize)], cb: F) -> Option<EachOrEveryTileMerge> where F: Fn(&X) -> K, K: Hash+Eq {
+        let mut ids = HashMap::new();
+        let mut seen = vec![];
+        let mut filters = vec![];
+        for (tile,len) in tiles.iter() {
+            if !tile.compatible(*len) { return None; }
+            /* identity only computed once per data value */
+            let mut data_ids = tile.data_cache();
+            let mut builder = EachOrEveryFilterBuilder::new();
+            for (pos,data_index) in tile.index_iter(*len).enumerate() {
+                let id = *data_ids.get_or_insert_with(data_index,|| {
+                    let next = ids.len();
+                    *ids.entry(cb(&tile.data[data_index])).or_insert(next)
+                });
+                if id == seen.len() { seen.push(false); }
+                if !seen[id] {
+                    seen[id] = true;
+                    builder.set(pos);
+                }
+            }
+            filters.push(builder.make(*len));
+        }
+        Some(EachOrEveryTileMerge { filters })
+    }
+
+    /* Which rows of each tile survived */
+    pub fn filters(&self) -> &[EachOrEveryFilter] { &self.filters }
+
+    /* Number of rows in the merged output */
+    pub fn count(&self) -> usize { self.filters.iter().map(|x| x.count()).sum() }
+
+    /* One column, given in tile order. Dictionaries are merged using cb as in EachOrEvery::concat.
+     * None if the wrong number of tiles is given or one has the wrong length.
+     */
+    pub fn merge<X: Clone,F,Z>(&self, tiles: &[&EachOrEvery<X>], cb: F) -> Option<EachOrEvery<X>> where F: Fn(&X) -> Z, Z: Hash+Eq {
+        if tiles.len() != self.filters.len() { return None; }
+        let mut parts = vec![];
+        for (tile,filter) in tiles.iter().zip(self.filters.iter()) {
+            if !tile.compatible(filter.len()) { return None; }
+            parts.push((tile.filter(filter),filter.count()));
+        }
+        let parts = parts.iter().map(|(part,len)| (part,*len)).collect::<Vec<_>>();
+        EachOrEvery::concat(&parts,cb)
+    }
+
+    /* Merges a group of columns per tile, each group in the same column order. */
+    pub fn merge_columns(&self, tiles: &[Vec<EachOrEveryColumn>]) -> Option<Vec<EachOrEveryColumn>> {
+        let width = tiles.first().map(|x| x.len()).unwrap_or(0);
+        if tiles.iter().any(|x| x.len() != width) { return None; }
+        (0..width).map(|i| {
+            match &tiles.first()?[i] {
+                EachOrEveryColumn::Number(_) => {
+                    let parts = tiles.iter().map(|x| match &x[i] { EachOrEveryColumn::Number(x) => Some(x), _ => None }).collect::<Option<Vec<_>>>()?;
+                    self.merge(&parts,|x| x.to_bits()).map(EachOrEveryColumn::Number)
+                },
+                EachOrEveryColumn::String(_) => {
+                    let parts = tiles.iter().map(|x| match &x[i] { EachOrEveryColumn::String(x) => Some(x), _ => None }).collect::<Option<Vec<_>>>()?;
+                    self.merge(&parts,|x| x.clone()).map(EachOrEveryColumn::String)
+                },
+                EachOrEveryColumn::Boolean(_) => {
+                    let parts = tiles.iter().map(|x| match &x[i] { EachOrEveryColumn::Boolean(x) => Some(x), _ => None }).collect::<Option<Vec<_>>>()?;
+                    self.merge(&parts,|x| *x).map(EachOrEveryColumn::Boolean)
+                }
+            }
+        }).collect()
+    }
+}
@@ -30,6 +30,31 @@ pub mod eoestruct {
 mod approxnumber;
 mod eoefilter;
 mod eachorevery;
+mod eoeprogression;
+mod eoeserde;
+mod eoebinary;
+mod eoesort;
+mod eoetilemerge;
+mod eoegroupby;
+mod eoestats;
+mod eoescan;
+mod eoeinterval;
+mod eoebump;
+mod eoebin;
 
-pub use crate::eachorevery::{ EachOrEvery, EachOrEveryGroupCompatible };
+pub use crate::eachorevery::{ EachOrEvery, EachOrEveryChunked, EachOrEveryGroupCompatible, EachOrEveryPositional };
 pub use crate::eoefilter::{ EachOrEveryFilter, EachOrEveryFilterBuilder };
+pub use crate::eoeprogression::{ EachOrEveryProgression, EachOrEveryFilteredProgression, EachOrEveryNumber };
+pub use crate::eoebinary::{ EachOrEveryColumn, eoe_to_binary, eoe_from_binary };
+pub use crate::eoesort::{ EachOrEverySortBuilder, EachOrEveryPermutation };
+pub use crate::eoetilemerge::{ EachOrEveryTileMerge };
+pub use crate::eoegroupby::{ EachOrEveryGroupBy };
+pub use crate::eoestats::{ EachOrEveryStats };
+pub use crate::eoeinterval::{ EachOrEveryOverlap, EachOrEveryIntervalIndex, EachOrEveryIntervalUnion, eoe_overlap_filter };
+pub use crate::eoebump::{ EachOrEveryBumpBuilder };
+pub use crate::eoebin::{ EachOrEveryBinBuilder, EachOrEveryBinning };
+
+#[cfg(test)]
+mod test {
+    mod eachoreverytest;
+}
@@ -0,0 +1,871 @@
+use crate::{EachOrEvery, EachOrEveryFilteredProgression, EachOrEveryIntervalUnion, EachOrEveryBinBuilder, EachOrEveryBinning, EachOrEveryBumpBuilder, EachOrEveryIntervalIndex, EachOrEveryOverlap, eoe_overlap_filter, EachOrEveryPositional, EachOrEveryStats, EachOrEveryGroupBy, EachOrEveryTileMerge, EachOrEveryChunked, EachOrEveryFilter, EachOrEveryFilterBuilder, EachOrEveryProgression, EachOrEverySortBuilder, EachOrEveryColumn, eoe_to_binary, eoe_from_binary};
+use std::{cell::Cell, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::json;
+
+fn values<X: Clone>(eoe: &EachOrEvery<X>, len: usize) -> Vec<X> {
+    eoe.iter(len).unwrap().cloned().collect()
+}
+
+fn filter_of(len: usize, positions: &[usize]) -> EachOrEveryFilter {
+    let mut builder = EachOrEveryFilterBuilder::new();
+    for pos in positions {
+        builder.set(*pos);
+    }
+    builder.make(len)
+}
+
+fn filter_positions(filter: &EachOrEveryFilter) -> Vec<usize> {
+    filter.filter_clone(&(0..filter.len()).collect::<Vec<_>>())
+}
+
+#[test]
+fn test_run_length_smoke() {
+    let eoe = EachOrEvery::runs(vec![("a",3),("b",0),("c",2),("a",1)]);
+    assert_eq!(Some(6),eoe.len());
+    assert_eq!(3,eoe.space());
+    assert_eq!(vec!["a","a","a","c","c","a"],values(&eoe,6));
+    assert_eq!(Some(&"c"),eoe.get(4));
+    assert_eq!(Some(&"a"),eoe.get(5));
+    assert_eq!(None,eoe.get(6));
+    let upper = eoe.map(|x| x.to_uppercase());
+    assert_eq!(3,upper.space());
+    assert_eq!(vec!["A","A","A","C","C","A"],values(&upper,6));
+    let indexed = eoe.index(|x| *x);
+    assert_eq!(2,indexed.space());
+    assert_eq!(values(&eoe,6),values(&indexed,6));
+}
+
+#[test]
+fn test_run_length_encode() {
+    let each = EachOrEvery::each(vec![1,1,1,2,2,1,1]);
+    let rle = each.run_length(|x| *x);
+    assert_eq!(2,rle.space());
+    assert_eq!(values(&each,7),values(&rle,7));
+    let filtered = each.filter(&filter_of(7,&[1,2,4,6])).run_length(|x| *x);
+    assert_eq!(vec![1,1,2,1],values(&filtered,4));
+}
+
+#[test]
+fn test_run_length_filter() {
+    let eoe = EachOrEvery::runs(vec![(1,3),(2,3),(3,3)]);
+    let filter = filter_of(9,&[0,2,3,4,8]);
+    let filtered = eoe.filter(&filter);
+    assert_eq!(vec![1,1,2,2,3],values(&filtered,5));
+    assert_eq!(3,filtered.space());
+    let odd = eoe.make_filter(9,|x| *x % 2 == 1);
+    assert_eq!(vec![0,1,2,6,7,8],filter_positions(&odd));
+    let mut demerged = eoe.demerge(9,|x| *x == 2);
+    demerged.sort_by_key(|(k,_)| *k);
+    assert_eq!(vec![0,1,2,6,7,8],filter_positions(&demerged[0].1));
+    assert_eq!(vec![3,4,5],filter_positions(&demerged[1].1));
+}
+
+#[test]
+fn test_run_length_zip() {
+    let a = EachOrEvery::runs(vec![(1,2),(2,4)]);
+    let b = EachOrEvery::runs(vec![(10,3),(20,3)]);
+    let sum = a.zip(&b,|x,y| x+y);
+    assert_eq!(3,sum.space());
+    assert_eq!(vec![11,11,12,22,22,22],values(&sum,6));
+    let each = EachOrEvery::each(vec![0,1,2,3,4,5]);
+    assert_eq!(vec![1,2,4,5,6,7],values(&each.zip(&a,|x,y| x+y),6));
+    let every = EachOrEvery::every(100);
+    let zipped = every.zip(&a,|x,y| x+y);
+    assert_eq!(2,zipped.space());
+    assert_eq!(vec![101,101,102,102,102,102],values(&zipped,6));
+}
+
+#[test]
+fn test_repeat() {
+    let every = EachOrEvery::every("x");
+    assert_eq!(None,every.len());
+    let repeat = every.to_each(4).unwrap();
+    assert_eq!(Some(4),repeat.len());
+    assert_eq!(1,repeat.space());
+    assert_eq!(vec!["x";4],values(&repeat,4));
+    assert_eq!(None,repeat.get(4));
+    assert!(repeat.iter(5).is_none());
+    let filtered = repeat.filter(&filter_of(4,&[1,3]));
+    assert_eq!(Some(2),filtered.len());
+    assert_eq!(1,filtered.space());
+    let demerged = repeat.demerge(4,|x| x.to_string());
+    assert_eq!(1,demerged.len());
+    assert_eq!(4,demerged[0].1.count());
+}
+
+#[test]
+fn test_repeat_zip() {
+    let repeat = EachOrEvery::repeat(10,3);
+    let every = EachOrEvery::every(1);
+    let zipped = every.zip(&repeat,|a,b| a+b);
+    assert_eq!(Some(3),zipped.len());
+    assert_eq!(1,zipped.space());
+    let zipped = repeat.zip(&EachOrEvery::repeat(5,3),|a,b| a-b);
+    assert_eq!(vec![5,5,5],values(&zipped,3));
+    let each = EachOrEvery::each(vec![1,2,3]);
+    assert_eq!(vec![11,12,13],values(&repeat.zip(&each,|a,b| a+b),3));
+    let indexed = each.filter(&filter_of(3,&[0,2]));
+    let zipped = indexed.zip(&EachOrEvery::repeat(10,2),|a,b| a*b);
+    assert_eq!(vec![10,30],values(&zipped,2));
+    assert_eq!(3,zipped.space());
+}
+
+#[test]
+fn test_progression() {
+    let bins = EachOrEveryProgression::new(1000.,50.,5);
+    assert_eq!(5,bins.len());
+    assert_eq!(Some(1100.),bins.get(2));
+    assert_eq!(None,bins.get(5));
+    assert_eq!(vec![1000.,1050.,1100.,1150.,1200.],bins.iter().collect::<Vec<_>>());
+    let scaled = bins.affine(2.,-2000.);
+    assert_eq!(vec![0.,100.,200.,300.,400.],scaled.iter().collect::<Vec<_>>());
+    let filtered = bins.filter(&filter_of(5,&[1,3,4]));
+    assert_eq!(vec![1050.,1150.,1200.],values(&filtered.to_each(),3));
+    match bins.filter(&filter_of(5,&[1,2,3])) {
+        EachOrEveryFilteredProgression::Progression(run) => {
+            assert_eq!(vec![1050.,1100.,1150.],run.iter().collect::<Vec<_>>());
+        },
+        EachOrEveryFilteredProgression::Each(_) => panic!("contiguous filter materialised")
+    }
+    assert!(matches!(bins.filter(&EachOrEveryFilter::all(5)),EachOrEveryFilteredProgression::Progression(_)));
+    assert!(bins.filter(&EachOrEveryFilter::none(5)).is_empty());
+    let labels = EachOrEveryProgression::new(1_usize,1,3).map(|x| format!("row {}",x));
+    assert_eq!(vec!["row 1","row 2","row 3"],values(&labels,3));
+}
+
+#[test]
+fn test_progression_zip() {
+    let rows = EachOrEveryProgression::new(0_i64,10,4);
+    let every = EachOrEvery::every(1);
+    assert_eq!(vec![1,11,21,31],values(&rows.zip(&every,|a,b| a+b).unwrap(),4));
+    let runs = EachOrEvery::runs(vec![(1,2),(2,2)]);
+    assert_eq!(vec![0,10,40,60],values(&rows.zip(&runs,|a,b| a*b).unwrap(),4));
+    /* mismatched lengths in either direction */
+    assert!(rows.zip(&EachOrEvery::each((0..10).collect::<Vec<i64>>()),|a,b| a+b).is_none());
+    assert!(rows.zip(&EachOrEvery::each(vec![1_i64,2]),|a,b| a+b).is_none());
+}
+
+fn round_trip<X>(eoe: &EachOrEvery<X>) -> EachOrEvery<X> where X: Serialize + DeserializeOwned {
+    serde_json::from_str(&serde_json::to_string(eoe).unwrap()).unwrap()
+}
+
+#[test]
+fn test_serde_round_trip() {
+    let each = EachOrEvery::each(vec!["a".to_string(),"b".to_string(),"a".to_string()]);
+    let indexed = each.index(|x| x.clone());
+    let runs = EachOrEvery::runs(vec![(true,3),(false,2)]);
+    for eoe in [&each,&indexed,&each.filter(&filter_of(3,&[0,2])),&indexed.filter(&filter_of(3,&[1]))] {
+        let len = eoe.len().unwrap();
+        let copy = round_trip(eoe);
+        assert_eq!(eoe.len(),copy.len());
+        assert_eq!(values(eoe,len),values(&copy,len));
+    }
+    assert_eq!(1,round_trip(&indexed.filter(&filter_of(3,&[1]))).space());
+    assert_eq!(2,round_trip(&indexed).space());
+    let copy = round_trip(&runs);
+    assert_eq!(2,copy.space());
+    assert_eq!(values(&runs,5),values(&copy,5));
+    let copy = round_trip(&EachOrEvery::repeat(1.5,7));
+    assert_eq!(Some(7),copy.len());
+    assert_eq!(1,copy.space());
+    assert_eq!(None,round_trip(&EachOrEvery::every(2)).len());
+}
+
+#[test]
+fn test_serde_format() {
+    let indexed = EachOrEvery::each(vec![5,6,5,5]).index(|x| *x);
+    let json = serde_json::to_value(&indexed).unwrap();
+    assert_eq!(json!({ "kind": "indexed", "data": [5,6], "index": [0,1,0,0] }),json);
+    let runs = EachOrEvery::runs(vec![(5,3),(6,1)]);
+    let json = serde_json::to_value(&runs).unwrap();
+    assert_eq!(json!({ "kind": "runs", "data": [5,6], "index": [0,3,1,4] }),json);
+    /* views only carry the data they use */
+    let filtered = EachOrEvery::each((0..10).collect::<Vec<i32>>()).filter(&filter_of(10,&[1,2]));
+    let json = serde_json::to_value(&filtered).unwrap();
+    assert_eq!(json!({ "kind": "indexed", "data": [1,2], "index": [0,1] }),json);
+    let runs = EachOrEvery::runs(vec![(5,3),(6,1),(7,2)]).slice(4..6).unwrap();
+    let json = serde_json::to_value(&runs).unwrap();
+    assert_eq!(json!({ "kind": "runs", "data": [7], "index": [0,2] }),json);
+    let bad = json!({ "kind": "indexed", "data": [5,6], "index": [0,2] });
+    assert!(serde_json::from_value::<EachOrEvery<i32>>(bad).is_err());
+    let bad = json!({ "kind": "repeat", "data": [5,6], "index": [2] });
+    assert!(serde_json::from_value::<EachOrEvery<i32>>(bad).is_err());
+    let bad = json!({ "kind": "runs", "data": [5,6], "index": [0,3,1,3] });
+    assert!(serde_json::from_value::<EachOrEvery<i32>>(bad).is_err());
+}
+
+fn binary_sample() -> Vec<(String,EachOrEveryColumn)> {
+    let names = (0..300).map(|i| format!("gene-{}",i%7)).collect::<Vec<_>>();
+    vec![
+        ("start".to_string(),EachOrEveryColumn::Number(EachOrEvery::each((0..300).map(|x| x as f64*1.5).collect()))),
+        ("name".to_string(),EachOrEveryColumn::String(EachOrEvery::each(names).index(|x| x.clone()))),
+        ("strand".to_string(),EachOrEveryColumn::Boolean(EachOrEvery::runs(vec![(true,100),(false,150),(true,50)]))),
+        ("height".to_string(),EachOrEveryColumn::Number(EachOrEvery::repeat(2.,300))),
+        ("colour".to_string(),EachOrEveryColumn::String(EachOrEvery::every("red".to_string()))),
+        ("focus".to_string(),EachOrEveryColumn::Boolean(EachOrEvery::each((0..300).map(|x| x%3 == 0).collect())))
+    ]
+}
+
+fn column_debug(column: &EachOrEveryColumn) -> (Option<usize>,usize,String) {
+    match column {
+        EachOrEveryColumn::Number(x) => (x.len(),x.space(),format!("{:?}",values(x,x.len().unwrap_or(1)))),
+        EachOrEveryColumn::String(x) => (x.len(),x.space(),format!("{:?}",values(x,x.len().unwrap_or(1)))),
+        EachOrEveryColumn::Boolean(x) => (x.len(),x.space(),format!("{:?}",values(x,x.len().unwrap_or(1))))
+    }
+}
+
+#[test]
+fn test_binary_round_trip() {
+    let columns = binary_sample();
+    let binary = eoe_to_binary(&columns).unwrap();
+    let copy = eoe_from_binary(&binary).unwrap();
+    assert_eq!(columns.len(),copy.len());
+    for ((name_a,col_a),(name_b,col_b)) in columns.iter().zip(copy.iter()) {
+        assert_eq!(name_a,name_b);
+        assert_eq!(column_debug(col_a),column_debug(col_b));
+    }
+    /* a filtered view is written without its unused data */
+    let big = EachOrEvery::each((0..1000).map(|x| x as f64).collect::<Vec<_>>());
+    let filtered = vec![("x".to_string(),EachOrEveryColumn::Number(big.filter(&filter_of(1000,&[3,500]))))];
+    let copy = eoe_from_binary(&eoe_to_binary(&filtered).unwrap()).unwrap();
+    assert_eq!((Some(2),2,"[3.0, 500.0]".to_string()),column_debug(&copy[0].1));
+    let empty = eoe_from_binary(&eoe_to_binary(&[]).unwrap()).unwrap();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_binary_fail() {
+    let mut columns = binary_sample();
+    let binary = eoe_to_binary(&columns).unwrap();
+    assert!(eoe_from_binary(&binary[..binary.len()-1]).is_err());
+    let mut extra = binary.clone();
+    extra.push(0);
+    assert!(eoe_from_binary(&extra).is_err());
+    let mut bad_magic = binary.clone();
+    bad_magic[0] = b'X';
+    assert!(eoe_from_binary(&bad_magic).is_err());
+    columns.push(("short".to_string(),EachOrEveryColumn::Number(EachOrEvery::each(vec![1.]))));
+    assert!(eoe_to_binary(&columns).is_err());
+}
+
+#[test]
+fn test_sort() {
+    let start = EachOrEvery::each(vec![30.,10.,20.,10.,30.]);
+    let priority = EachOrEvery::each(vec![1,2,1,2,1,0,0,1,0,2]).filter(&filter_of(10,&[0,1,2,3,5]));
+    let name = EachOrEvery::each(vec!["a","b","c","d","e"]);
+    let perm = EachOrEverySortBuilder::new(5)
+        .key(&start,|a,b| a.partial_cmp(b).unwrap())
+        .key(&EachOrEvery::every(0),|a,b| a.cmp(b))
+        .key(&priority,|a,b| b.cmp(a))
+        .sort();
+    assert_eq!(&[1,3,2,0,4],perm.positions());
+    let sorted = perm.apply(&name);
+    assert_eq!(vec!["b","d","c","a","e"],values(&sorted,5));
+    assert_eq!(5,sorted.space());
+    assert_eq!(vec![2,2,1,1,0],values(&perm.apply(&priority),5));
+    assert_eq!(vec!["x";5],values(&perm.apply(&EachOrEvery::every("x")),5));
+    let runs = EachOrEvery::runs(vec![(1,2),(2,3)]);
+    assert_eq!(vec![1,2,2,1,2],values(&perm.apply(&runs),5));
+}
+
+#[test]
+fn test_gather() {
+    let each = EachOrEvery::each(vec!["a","b","c","d"]);
+    let gathered = each.gather(&[3,0,0,2,3]).unwrap();
+    assert_eq!(vec!["d","a","a","c","d"],values(&gathered,5));
+    assert_eq!(4,gathered.space());
+    let again = gathered.gather(&[1,4,4]).unwrap();
+    assert_eq!(vec!["a","d","d"],values(&again,3));
+    assert_eq!(4,again.space());
+    assert!(each.gather(&[4]).is_none());
+    let runs = EachOrEvery::runs(vec![(1,2),(2,2)]);
+    assert_eq!(vec![2,1,2],values(&runs.gather(&[3,0,2]).unwrap(),3));
+    assert_eq!(Some(2),EachOrEvery::repeat(0,10).gather(&[9,9]).unwrap().len());
+    assert_eq!(None,EachOrEvery::every(0).gather(&[100]).unwrap().len());
+    assert_eq!(Some(0),each.gather(&[]).unwrap().len());
+}
+
+#[test]
+fn test_slice() {
+    let each = EachOrEvery::each((0..10).collect::<Vec<_>>());
+    let slice = each.slice(3..7).unwrap();
+    assert_eq!(Some(4),slice.len());
+    assert_eq!(10,slice.space());
+    assert_eq!(vec![3,4,5,6],values(&slice,4));
+    assert_eq!(None,slice.get(4));
+    assert_eq!(vec![4,5],values(&slice.slice(1..3).unwrap(),2));
+    assert_eq!(vec![30,40,50,60],values(&slice.map(|x| x*10),4));
+    assert_eq!(vec![5,6],values(&slice.filter(&filter_of(4,&[2,3])),2));
+    assert_eq!(vec![3,5,7,9],values(&slice.zip(&each.slice(0..4).unwrap(),|a,b| a+b),4));
+    assert_eq!(vec![4,6],filter_positions(&each.make_filter(10,|x| x%2 == 0).slice(3..7).unwrap()).iter().map(|x| x+3).collect::<Vec<_>>());
+    assert!(each.slice(5..11).is_none());
+    let indexed = each.gather(&[9,8,7,6,5]).unwrap();
+    let slice = indexed.slice(1..4).unwrap();
+    assert_eq!(vec![8,7,6],values(&slice,3));
+    assert_eq!(vec![7],values(&slice.gather(&[1]).unwrap(),1));
+    let runs = EachOrEvery::runs(vec![("a",3),("b",3),("c",3)]);
+    let slice = runs.slice(2..7).unwrap();
+    assert_eq!(vec!["a","b","b","b","c"],values(&slice,5));
+    assert_eq!(Some(2),EachOrEvery::repeat(0,5).slice(1..3).unwrap().len());
+}
+
+#[test]
+fn test_filter_slice() {
+    let filter = filter_of(10,&[0,1,2,5,6,9]);
+    assert_eq!(vec![0,3,4],filter_positions(&filter.slice(2..7).unwrap()));
+    assert_eq!(3,filter.slice(2..7).unwrap().count());
+    assert_eq!(Vec::<usize>::new(),filter_positions(&filter.slice(3..5).unwrap()));
+    assert_eq!(vec![0,1],filter_positions(&filter.slice(5..7).unwrap()));
+    assert_eq!(2,EachOrEveryFilter::all(10).slice(8..10).unwrap().count());
+    assert!(filter.slice(8..11).is_none());
+}
+
+#[test]
+fn test_concat() {
+    let a = EachOrEvery::each(vec!["x","y"]);
+    let b = EachOrEvery::each(vec!["z"]);
+    let joined = EachOrEvery::concat(&[(&a,2),(&b,1),(&a.slice(1..2).unwrap(),1)],|x| *x).unwrap();
+    assert_eq!(vec!["x","y","z","y"],values(&joined,4));
+    assert_eq!(4,joined.space());
+    let every = EachOrEvery::every("x");
+    let joined = EachOrEvery::concat(&[(&every,3),(&every,2)],|x| *x).unwrap();
+    assert_eq!(None,joined.len());
+    let joined = EachOrEvery::concat(&[(&every,3),(&EachOrEvery::repeat("x",2),2)],|x| *x).unwrap();
+    assert_eq!(Some(5),joined.len());
+    assert_eq!(1,joined.space());
+    let joined = EachOrEvery::concat(&[(&every,3),(&EachOrEvery::runs(vec![("x",1),("y",2)]),3)],|x| *x).unwrap();
+    assert_eq!(vec!["x","x","x","x","y","y"],values(&joined,6));
+    assert_eq!(2,joined.space());
+    assert!(EachOrEvery::concat(&[(&a,3)],|x| *x).is_none());
+    /* empty parts don't spoil a constant result */
+    let joined = EachOrEvery::concat(&[(&EachOrEvery::repeat("a",0),0),(&EachOrEvery::repeat("b",3),3)],|x| *x).unwrap();
+    assert_eq!(Some(3),joined.len());
+    assert_eq!(1,joined.space());
+    assert_eq!(vec!["b","b","b"],values(&joined,3));
+    let joined = EachOrEvery::concat(&[(&EachOrEvery::every("b"),2),(&EachOrEvery::every("a"),0)],|x| *x).unwrap();
+    assert_eq!(None,joined.len());
+    assert_eq!(1,joined.space());
+}
+
+#[test]
+fn test_concat_dictionary() {
+    let a = EachOrEvery::each(vec!["p","q","r","q"]).index(|x| *x).filter(&filter_of(4,&[1,3]));
+    let b = EachOrEvery::each(vec!["q","s","p"]);
+    let joined = EachOrEvery::concat(&[(&a,2),(&b,3),(&EachOrEvery::every("s"),2)],|x| *x).unwrap();
+    assert_eq!(vec!["q","q","q","s","p","s","s"],values(&joined,7));
+    assert_eq!(3,joined.space());
+}
+
+fn chunked_sample() -> EachOrEveryChunked<i32> {
+    let mut chunked = EachOrEveryChunked::new();
+    assert!(chunked.push(&EachOrEvery::each(vec![1,2,3]),3));
+    assert!(chunked.push(&EachOrEvery::every(7),2));
+    assert!(chunked.push(&EachOrEvery::each(vec![]),0));
+    assert!(chunked.push(&EachOrEvery::runs(vec![(4,2),(5,1)]),3));
+    assert!(!chunked.push(&EachOrEvery::each(vec![1]),2));
+    chunked
+}
+
+#[test]
+fn test_chunked() {
+    let chunked = chunked_sample();
+    assert_eq!(8,chunked.len());
+    assert_eq!(vec![1,2,3,7,7,4,4,5],chunked.iter().cloned().collect::<Vec<_>>());
+    assert_eq!(Some(&7),chunked.get(4));
+    assert_eq!(Some(&4),chunked.get(5));
+    assert_eq!(None,chunked.get(8));
+    let doubled = chunked.map(|x| x*2);
+    assert_eq!(vec![2,4,6,14,14,8,8,10],doubled.iter().cloned().collect::<Vec<_>>());
+    let filtered = chunked.filter(&filter_of(8,&[1,2,3,7]));
+    assert_eq!(4,filtered.len());
+    assert_eq!(vec![2,3,7,5],filtered.iter().cloned().collect::<Vec<_>>());
+    let flat = chunked.flatten(|x| *x);
+    assert_eq!(vec![1,2,3,7,7,4,4,5],values(&flat,8));
+}
+
+#[test]
+fn test_chunked_zip() {
+    let chunked = chunked_sample();
+    let each = EachOrEvery::each((0..8).collect::<Vec<_>>());
+    let zipped = chunked.zip(&each,|a,b| a*10+b).unwrap();
+    assert_eq!(vec![10,21,32,73,74,45,46,57],zipped.iter().cloned().collect::<Vec<_>>());
+    assert!(chunked.zip(&EachOrEvery::each(vec![1]),|a,b| a+b).is_none());
+    let mut other = EachOrEveryChunked::new();
+    other.push(&EachOrEvery::each(vec![100,200]),2);
+    other.push(&EachOrEvery::repeat(300,6),6);
+    let zipped = chunked.zip_chunked(&other,|a,b| a+b).unwrap();
+    assert_eq!(vec![101,202,303,307,307,304,304,305],zipped.iter().cloned().collect::<Vec<_>>());
+    assert_eq!(4,zipped.chunks().len());
+}
+
+#[test]
+fn test_tile_merge() {
+    let id_a = EachOrEvery::each(vec!["g1","g2","g3"]);
+    let id_b = EachOrEvery::each(vec!["g3","g4","g4","g5"]);
+    let id_c = EachOrEvery::every("g5");
+    let merge = EachOrEveryTileMerge::new(&[(&id_a,3),(&id_b,4),(&id_c,2)],|x| *x).unwrap();
+    assert_eq!(5,merge.count());
+    assert_eq!(vec![0,1,2],filter_positions(&merge.filters()[0]));
+    assert_eq!(vec![1,3],filter_positions(&merge.filters()[1]));
+    assert_eq!(0,merge.filters()[2].count());
+    let start_a = EachOrEvery::each(vec![10,20,30]);
+    let start_b = EachOrEvery::each(vec![30,40,40,50]);
+    let start_c = EachOrEvery::every(50);
+    let start = merge.merge(&[&start_a,&start_b,&start_c],|x| *x).unwrap();
+    assert_eq!(vec![10,20,30,40,50],values(&start,5));
+    assert!(merge.merge(&[&start_a,&start_b],|x| *x).is_none());
+    assert!(EachOrEveryTileMerge::new(&[(&id_a,4)],|x| *x).is_none());
+}
+
+#[test]
+fn test_tile_merge_columns() {
+    let id_a = EachOrEvery::each(vec![1,2]);
+    let id_b = EachOrEvery::each(vec![2,3]);
+    let merge = EachOrEveryTileMerge::new(&[(&id_a,2),(&id_b,2)],|x| *x).unwrap();
+    let tile = |name: Vec<&str>, start: Vec<f64>| vec![
+        EachOrEveryColumn::String(EachOrEvery::each(name.iter().map(|x| x.to_string()).collect())),
+        EachOrEveryColumn::Number(EachOrEvery::each(start)),
+        EachOrEveryColumn::Boolean(EachOrEvery::every(true))
+    ];
+    let merged = merge.merge_columns(&[tile(vec!["a","b"],vec![1.,2.]),tile(vec!["b","c"],vec![2.,3.])]).unwrap();
+    assert_eq!((Some(3),3,"[\"a\", \"b\", \"c\"]".to_string()),column_debug(&merged[0]));
+    assert_eq!((Some(3),3,"[1.0, 2.0, 3.0]".to_string()),column_debug(&merged[1]));
+    assert_eq!((None,1,"[true]".to_string()),column_debug(&merged[2]));
+}
+
+fn eoe_hash<X: Hash>(eoe: &EachOrEvery<X>) -> u64 {
+    let mut state = DefaultHasher::new();
+    eoe.hash(&mut state);
+    state.finish()
+}
+
+#[test]
+fn test_content_equality() {
+    let each = EachOrEvery::each(vec!["a","a","b","b","b"]);
+    let same = [
+        each.index(|x| *x),
+        each.run_length(|x| *x),
+        EachOrEvery::each(vec!["z","a","a","b","b","b"]).slice(1..6).unwrap(),
+        EachOrEvery::concat(&[(&EachOrEvery::repeat("a",2),2),(&EachOrEvery::every("b"),3)],|x| *x).unwrap()
+    ];
+    for other in same.iter() {
+        assert!(each == *other);
+        assert_eq!(eoe_hash(&each),eoe_hash(other));
+    }
+    assert!(each == each.clone());
+    assert!(each.content_eq(&each.clone()));
+    assert!(each.content_eq(&each.slice(0..5).unwrap()));
+    assert!(!each.content_eq(&each.slice(0..4).unwrap()));
+    assert!(each != EachOrEvery::each(vec!["a","a","b","b"]));
+    assert!(each != EachOrEvery::each(vec!["a","b","b","b","b"]));
+    assert_ne!(eoe_hash(&each),eoe_hash(&EachOrEvery::each(vec!["a","b","b","b","b"])));
+    let every = EachOrEvery::every(1);
+    assert!(every == EachOrEvery::every(1));
+    assert!(every != EachOrEvery::every(2));
+    assert!(every != EachOrEvery::repeat(1,1));
+    assert!(EachOrEvery::repeat(1,3) == EachOrEvery::each(vec![1,1,1]));
+    assert_eq!(eoe_hash(&EachOrEvery::repeat(1,3)),eoe_hash(&EachOrEvery::each(vec![1,1,1])));
+    /* NaN is unequal however it is stored, even when data is shared */
+    let nan = EachOrEvery::each(vec![1.,f64::NAN]);
+    assert!(nan != nan.clone());
+    assert!(nan != EachOrEvery::each(vec![1.,f64::NAN]));
+    assert!(nan != nan.index(|x| x.to_bits()));
+    assert!(EachOrEvery::repeat(f64::NAN,2) != EachOrEvery::repeat(f64::NAN,2));
+    /* runs and constants hash without visiting each position */
+    let huge = EachOrEvery::runs(vec![(1,1_000_000_000_000),(2,1_000_000_000_000)]);
+    assert_eq!(eoe_hash(&huge),eoe_hash(&EachOrEvery::runs(vec![(1,1_000_000_000_000),(2,1_000_000_000_000)])));
+    assert_ne!(eoe_hash(&EachOrEvery::repeat(1,1_000_000_000_000)),eoe_hash(&EachOrEvery::repeat(1,999_999_999_999)));
+}
+
+#[test]
+fn test_compact() {
+    let names = EachOrEvery::each((0..100).map(|x| format!("gene-{}",x%10)).collect::<Vec<_>>());
+    let indexed = names.index(|x| x.clone());
+    let filtered = indexed.filter(&filter_of(100,&[1,11,21,2,3]));
+    assert_eq!(10,filtered.space());
+    let compact = filtered.compact();
+    assert_eq!(3,compact.space());
+    assert!(compact == filtered);
+    let sparse = names.filter(&filter_of(100,&[5,50,95]));
+    assert_eq!(100,sparse.space());
+    let compact = sparse.compact();
+    assert_eq!(3,compact.space());
+    assert!(compact == sparse);
+    assert_eq!(100,names.slice(0..60).unwrap().auto_compact().space());
+    assert_eq!(40,names.slice(0..40).unwrap().auto_compact().space());
+    let runs = EachOrEvery::runs(vec![(1,2),(2,2),(3,2),(4,2),(5,2)]).slice(3..5).unwrap();
+    assert_eq!(5,runs.space());
+    assert_eq!(2,runs.compact().space());
+    assert_eq!(2,runs.auto_compact().space());
+    assert!(runs.compact() == runs);
+}
+
+#[test]
+fn test_zip_shared_index() {
+    let biotype = EachOrEvery::each((0..100).map(|x| x%4).collect::<Vec<_>>()).index(|x| *x);
+    let colour = biotype.map(|x| x*10);
+    let filter = filter_of(100,&[1,2,5,6,7,9]);
+    let biotype = biotype.filter(&filter);
+    let colour = colour.filter(&filter);
+    let zipped = biotype.zip(&colour,|a,b| a+b);
+    assert_eq!(4,zipped.space());
+    assert_eq!(vec![11,22,11,22,33,11],values(&zipped,6));
+    let again = zipped.zip(&biotype,|a,b| a-b);
+    assert_eq!(4,again.space());
+    assert_eq!(vec![10,20,10,20,30,10],values(&again,6));
+    let start = EachOrEvery::each((0..100).collect::<Vec<_>>()).filter(&filter);
+    let zipped = start.zip(&start.map(|x| x+1),|a,b| a*b);
+    assert_eq!(6,zipped.space());
+    assert_eq!(vec![2,6,30,42,56,90],values(&zipped,6));
+}
+
+#[test]
+fn test_dictionary_predicates() {
+    let calls = Cell::new(0);
+    let biotype = EachOrEvery::each((0..1000).map(|x| ["protein_coding","lncRNA","miRNA"][x%3]).collect::<Vec<_>>()).index(|x| *x);
+    let coding = biotype.make_filter(1000,|x| { calls.set(calls.get()+1); x.contains("coding") });
+    assert_eq!(3,calls.get());
+    assert_eq!(334,coding.count());
+    calls.set(0);
+    let short = biotype.map_positions(1000,|x| { calls.set(calls.get()+1); x.len() < 6 }).unwrap();
+    assert_eq!(3,calls.get());
+    assert_eq!(1000,short.len());
+    assert_eq!(vec![false,false,true,false],short[..4].to_vec());
+    calls.set(0);
+    let sparse = biotype.filter(&filter_of(1000,&[0,3,6]));
+    assert_eq!(vec![1,1,1],sparse.map_positions(3,|x| { calls.set(calls.get()+1); x.len()/14 }).unwrap());
+    assert_eq!(1,calls.get());
+    assert_eq!(vec![true,true],EachOrEvery::every(1).map_positions(2,|x| *x == 1).unwrap());
+    assert!(biotype.map_positions(3,|x| x.len()).is_none());
+}
+
+#[test]
+fn test_value_counts() {
+    let biotype = EachOrEvery::each(vec!["lncRNA","protein_coding","lncRNA","miRNA","protein_coding","lncRNA"]);
+    let expected = vec![("lncRNA",3),("protein_coding",2),("miRNA",1)];
+    assert_eq!(expected,biotype.value_counts(6,|x| *x));
+    assert_eq!(expected,biotype.index(|x| *x).value_counts(6,|x| *x));
+    assert_eq!(expected,biotype.run_length(|x| *x).value_counts(6,|x| *x));
+    assert_eq!(vec![("lncRNA",3),("miRNA",1),("protein_coding",2)],biotype.value_counts_sorted(6,|x| *x));
+    assert_eq!(vec![(6,3),(14,2),(5,1)],biotype.index(|x| *x).value_counts(6,|x| x.len()));
+    /* dictionary entries which are filtered away are not reported */
+    let calls = Cell::new(0);
+    let filtered = biotype.index(|x| *x).filter(&filter_of(6,&[3,4]));
+    assert_eq!(vec![("miRNA",1),("protein_coding",1)],filtered.value_counts(2,|x| { calls.set(calls.get()+1); *x }));
+    assert_eq!(2,calls.get());
+    assert_eq!(vec![("forward",1000)],EachOrEvery::every("forward").value_counts(1000,|x| *x));
+    assert_eq!(vec![("forward",7)],EachOrEvery::repeat("forward",7).value_counts(7,|x| *x));
+    assert!(EachOrEvery::every("forward").value_counts(0,|x| *x).is_empty());
+    /* a few rows of a large dictionary */
+    let big = EachOrEvery::each((0..1000).map(|x| x%7).collect::<Vec<_>>()).index(|x| *x).filter(&filter_of(1000,&[3,10,17,501]));
+    assert_eq!(vec![(3,3),(4,1)],big.value_counts(4,|x| *x));
+}
+
+#[test]
+fn test_group_by() {
+    let biotype = EachOrEvery::each(vec!["lncRNA","coding","lncRNA","miRNA","coding","lncRNA"]).index(|x| *x);
+    let length = EachOrEvery::each(vec![3.,10.,5.,1.,f64::NAN,4.]);
+    let name = EachOrEvery::each(vec!["a","b","c","d","e","f"]);
+    let calls = Cell::new(0);
+    let groups = EachOrEveryGroupBy::new(&biotype,6,|x| { calls.set(calls.get()+1); x.to_string() }).unwrap();
+    assert_eq!(3,calls.get());
+    assert_eq!(3,groups.len());
+    assert_eq!(vec!["lncRNA","coding","miRNA"],values(&groups.keys(),3));
+    assert_eq!(vec![3,2,1],values(&groups.count(),3));
+    assert_eq!(vec!["a","b","d"],values(&groups.first(&name).unwrap(),3));
+    assert_eq!(vec!["f","e","d"],values(&groups.last(&name).unwrap(),3));
+    assert_eq!(vec![3.,10.,1.],values(&groups.min(&length).unwrap(),3));
+    assert_eq!(vec![5.,10.,1.],values(&groups.max(&length).unwrap(),3));
+    assert_eq!(vec![12.,1.],values(&groups.sum(&length).unwrap(),3).iter().step_by(2).cloned().collect::<Vec<_>>());
+    assert_eq!(4.,*groups.mean(&length).unwrap().get(0).unwrap());
+    assert_eq!(vec!["acf","be","d"],values(&groups.fold(&name,String::new(),|s,x| s.push_str(x)).unwrap(),3));
+    /* selections share data with the input */
+    assert_eq!(6,groups.first(&name).unwrap().space());
+    /* constant columns stay constant */
+    let every = EachOrEvery::every(2.);
+    assert_eq!(None,groups.max(&every).unwrap().len());
+    assert_eq!(vec![6.,4.,2.],values(&groups.sum(&every).unwrap(),3));
+    let single = EachOrEveryGroupBy::new(&EachOrEvery::every("forward"),6,|x| *x).unwrap();
+    assert_eq!(vec!["forward"],values(&single.keys(),1));
+    assert_eq!(vec![6],values(&single.count(),1));
+    assert_eq!(vec![10.],values(&single.max(&length).unwrap(),1));
+    /* mismatched lengths */
+    assert!(EachOrEveryGroupBy::new(&biotype,5,|x| *x).is_none());
+    let short = EachOrEvery::each(vec![1.,2.]);
+    assert!(groups.sum(&short).is_none());
+    assert!(groups.mean(&EachOrEvery::repeat(1.,2)).is_none());
+    assert!(groups.first(&short).is_none());
+    assert!(groups.fold(&short,0.,|a,b| *a += *b).is_none());
+}
+
+#[test]
+fn test_stats() {
+    let signal = EachOrEvery::each(vec![2.,4.,4.,f64::NAN,4.,5.,5.,7.,9.]);
+    let stats = EachOrEveryStats::new(&signal,9).unwrap();
+    assert_eq!(8,stats.count());
+    assert_eq!(1,stats.nan_count());
+    assert_eq!(Some(2.),stats.min());
+    assert_eq!(Some(9.),stats.max());
+    assert_eq!(40.,stats.sum());
+    assert_eq!(Some(5.),stats.mean());
+    assert_eq!(Some(4.),stats.variance());
+    /* indexed columns give the same answer */
+    let indexed = EachOrEveryStats::new(&signal.index(|x| x.to_bits()),9).unwrap();
+    assert_eq!(8,indexed.count());
+    assert!((indexed.variance().unwrap()-4.).abs() < 1e-9);
+    let filtered = EachOrEveryStats::new_filtered(&signal,&filter_of(9,&[0,1,3,8])).unwrap();
+    assert_eq!((3,1,15.),(filtered.count(),filtered.nan_count(),filtered.sum()));
+    assert_eq!(Some(9.),filtered.max());
+    let window = EachOrEveryStats::new_filtered(&signal.slice(1..9).unwrap(),&filter_of(8,&[0,6,7])).unwrap();
+    assert_eq!((3,0,20.),(window.count(),window.nan_count(),window.sum()));
+    let indexed_filtered = EachOrEveryStats::new_filtered(&signal.index(|x| x.to_bits()),&filter_of(9,&[0,1,3,8])).unwrap();
+    assert_eq!((3,1,15.),(indexed_filtered.count(),indexed_filtered.nan_count(),indexed_filtered.sum()));
+    let every = EachOrEveryStats::new(&EachOrEvery::every(1.5),1_000_000_000).unwrap();
+    assert_eq!(1_000_000_000,every.count());
+    assert_eq!(Some(0.),every.variance());
+    assert_eq!(Some(1.5),every.mean());
+    let empty = EachOrEveryStats::new_filtered(&EachOrEvery::every(1.5),&EachOrEveryFilter::none(10)).unwrap();
+    assert_eq!((0,None,None),(empty.count(),empty.min(),empty.mean()));
+    assert!(EachOrEveryStats::new(&signal,8).is_none());
+    /* infinite values (eg log of zero) give infinite sum and mean, and NaN variance */
+    let log = EachOrEveryStats::new(&EachOrEvery::each(vec![f64::INFINITY,1.,3.]),3).unwrap();
+    assert_eq!((3,f64::INFINITY,Some(f64::INFINITY)),(log.count(),log.sum(),log.mean()));
+    assert_eq!((Some(1.),Some(f64::INFINITY)),(log.min(),log.max()));
+    assert!(log.variance().unwrap().is_nan());
+    let log = EachOrEveryStats::new(&EachOrEvery::each(vec![f64::NEG_INFINITY,2.,f64::NEG_INFINITY]).index(|x| x.to_bits()),3).unwrap();
+    assert_eq!(Some(f64::NEG_INFINITY),log.mean());
+}
+
+#[test]
+fn test_scan() {
+    let height = EachOrEvery::each(vec![2.,1.,3.,f64::NAN,2.]);
+    assert_eq!(vec![2.,3.,6.],values(&height.cumulative_sum(5).unwrap().slice(0..3).unwrap(),3));
+    assert_eq!(vec![2.,2.,3.,3.,3.],values(&height.cumulative_max(5).unwrap(),5));
+    assert_eq!(vec![2.,1.,1.,1.,1.],values(&height.cumulative_min(5).unwrap(),5));
+    assert_eq!(vec![2.,1.,1.,1.,1.],values(&height.index(|x| x.to_bits()).cumulative_min(5).unwrap(),5));
+    assert_eq!(5,height.cumulative_max(5).unwrap().space());
+    let labels = EachOrEvery::each(vec!["a","b","c"]).index(|x| *x);
+    assert_eq!(vec!["a","ab","abc"],values(&labels.scan(3,String::new(),|acc,x| format!("{}{}",acc,x)).unwrap(),3));
+    let every = EachOrEvery::every(1.5);
+    assert_eq!(vec![1.5,3.,4.5],values(&every.cumulative_sum(3).unwrap(),3));
+    assert_eq!(Some(3),every.cumulative_max(3).unwrap().len());
+    assert!(height.cumulative_sum(4).is_none());
+}
+
+#[test]
+fn test_adjacent() {
+    let start = EachOrEvery::each(vec![10.,25.,40.]);
+    let end = EachOrEvery::each(vec![20.,30.,45.]);
+    let gaps = end.adjacent_zip(&start,3,|end,next| next.map(|start| start-end)).unwrap();
+    assert_eq!(vec![Some(5.),Some(10.),None],values(&gaps,3));
+    assert_eq!(vec![15.,15.,0.],values(&start.adjacent(3,|x,next| next.map(|y| y-x).unwrap_or(0.)).unwrap(),3));
+    /* runs and constants only call once per distinct pair */
+    let calls = Cell::new(0);
+    let strand = EachOrEvery::runs(vec![(1,1000),(-1,1000)]);
+    let change = strand.adjacent(2000,|x,next| { calls.set(calls.get()+1); next.map(|y| x != y).unwrap_or(false) }).unwrap();
+    assert_eq!(4,calls.get());
+    assert_eq!(Some(2000),change.len());
+    assert_eq!(4,change.space());
+    assert_eq!(1,change.make_filter(2000,|x| *x).positions().collect::<Vec<_>>().len());
+    assert!(*change.get(999).unwrap());
+    calls.set(0);
+    let last = EachOrEvery::every(1).adjacent(1_000_000,|_,next| { calls.set(calls.get()+1); next.is_none() }).unwrap();
+    assert_eq!(2,calls.get());
+    assert_eq!(vec![999_999],last.make_filter(1_000_000,|x| *x).positions().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_map_with_position() {
+    let colour = EachOrEvery::each(vec!["red","blue","red","red"]).index(|x| *x);
+    let stripes = colour.map_with_position(4,EachOrEveryPositional::Dependent,|pos,x| format!("{}{}",x,pos%2)).unwrap();
+    assert_eq!(vec!["red0","blue1","red0","red1"],values(&stripes,4));
+    let calls = Cell::new(0);
+    let upper = colour.map_with_position(4,EachOrEveryPositional::Independent,|pos,x| { calls.set(calls.get()+1); format!("{}@{}",x,pos) }).unwrap();
+    assert_eq!(2,calls.get());
+    assert_eq!(vec!["red@0","blue@1","red@0","red@0"],values(&upper,4));
+    assert_eq!(2,upper.space());
+    let runs = EachOrEvery::runs(vec![("a",2),("b",3)]);
+    assert_eq!(vec!["a0","a0","b2","b2","b2"],values(&runs.map_with_position(5,EachOrEveryPositional::Independent,|pos,x| format!("{}{}",x,pos)).unwrap(),5));
+    let every = EachOrEvery::every(7);
+    assert_eq!(None,every.map_with_position(3,EachOrEveryPositional::Independent,|_,x| x+1).unwrap().len());
+    assert_eq!(vec![7,8,9],values(&every.map_with_position(3,EachOrEveryPositional::Dependent,|pos,x| x+pos).unwrap(),3));
+    assert!(colour.map_with_position(3,EachOrEveryPositional::Dependent,|pos,_| pos).is_none());
+}
+
+#[test]
+fn test_zip_with_position() {
+    let name = EachOrEvery::each(vec!["BRCA2","TP53","BRCA2"]).index(|x| *x);
+    let strand = EachOrEvery::every("+");
+    let labels = name.zip_with_position(&strand,3,EachOrEveryPositional::Dependent,|pos,x,y| format!("{}. {}{}",pos+1,x,y)).unwrap();
+    assert_eq!(vec!["1. BRCA2+","2. TP53+","3. BRCA2+"],values(&labels,3));
+    let calls = Cell::new(0);
+    let plain = name.zip_with_position(&strand,3,EachOrEveryPositional::Independent,|_,x,y| { calls.set(calls.get()+1); format!("{}{}",x,y) }).unwrap();
+    assert_eq!(2,calls.get());
+    assert_eq!(vec!["BRCA2+","TP53+","BRCA2+"],values(&plain,3));
+    let both = strand.zip_with_position(&EachOrEvery::every(1),3,EachOrEveryPositional::Independent,|_,x,y| format!("{}{}",x,y)).unwrap();
+    assert_eq!(None,both.len());
+    let runs = EachOrEvery::runs(vec![("a",3),("b",2)]);
+    let repeated = runs.zip_with_position(&EachOrEvery::repeat(1,5),5,EachOrEveryPositional::Independent,|_,x,y| format!("{}{}",x,y)).unwrap();
+    assert_eq!(vec!["a1","a1","a1","b1","b1"],values(&repeated,5));
+    assert_eq!(2,repeated.space());
+    assert!(name.zip_with_position(&strand,4,EachOrEveryPositional::Dependent,|pos,_,_| pos).is_none());
+}
+
+#[test]
+fn test_overlap_filter() {
+    let start = EachOrEvery::each(vec![0.,10.,20.,30.,f64::NAN]);
+    let end = EachOrEvery::each(vec![10.,20.,30.,40.,50.]);
+    let half_open = eoe_overlap_filter(&start,&end,5,20.,30.,EachOrEveryOverlap::HalfOpen).unwrap();
+    assert_eq!(vec![2],filter_positions(&half_open));
+    let closed = eoe_overlap_filter(&start,&end,5,20.,30.,EachOrEveryOverlap::Closed).unwrap();
+    assert_eq!(vec![1,2,3],filter_positions(&closed));
+    assert_eq!(vec![3],filter_positions(&eoe_overlap_filter(&start,&end,5,35.,35.,EachOrEveryOverlap::HalfOpen).unwrap()));
+    /* fixed-width features */
+    let width = EachOrEvery::every(1000.);
+    let single = EachOrEvery::each(vec![5.,2000.,500.]);
+    let ends = single.zip(&width,|s,w| s+w);
+    assert_eq!(vec![0,2],filter_positions(&eoe_overlap_filter(&single,&ends,3,1000.,1500.,EachOrEveryOverlap::Closed).unwrap()));
+    let everywhere = eoe_overlap_filter(&EachOrEvery::every(0.),&EachOrEvery::every(1e9),1_000_000,5.,6.,EachOrEveryOverlap::HalfOpen).unwrap();
+    assert_eq!(1_000_000,everywhere.count());
+    let nowhere = eoe_overlap_filter(&EachOrEvery::every(0.),&end,5,60.,70.,EachOrEveryOverlap::HalfOpen).unwrap();
+    assert_eq!(0,nowhere.count());
+    assert!(eoe_overlap_filter(&start,&end,4,0.,1.,EachOrEveryOverlap::HalfOpen).is_none());
+}
+
+#[test]
+fn test_interval_index() {
+    /* pseudo-random intervals, checked against the linear scan */
+    let mut seed = 12345_u64;
+    let mut random = move |n: u64| { seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); ((seed >> 33) % n) as f64 };
+    let starts = (0..500).map(|_| random(10000)).collect::<Vec<_>>();
+    let start = EachOrEvery::each(starts.clone());
+    let end = EachOrEvery::each(starts.iter().map(|s| s+random(300)).collect());
+    for mode in [EachOrEveryOverlap::HalfOpen,EachOrEveryOverlap::Closed] {
+        let index = EachOrEveryIntervalIndex::new(&start,&end,500,mode).unwrap();
+        for _ in 0..50 {
+            let window_start = random(10000);
+            let window_end = window_start + random(500);
+            let expected = eoe_overlap_filter(&start,&end,500,window_start,window_end,mode).unwrap();
+            assert_eq!(filter_positions(&expected),filter_positions(&index.overlapping(window_start,window_end)));
+        }
+    }
+    let start = EachOrEvery::each(vec![10.,0.,f64::NAN,20.]);
+    let end = EachOrEvery::every(20.);
+    let half_open = EachOrEveryIntervalIndex::new(&start,&end,4,EachOrEveryOverlap::HalfOpen).unwrap();
+    assert_eq!(vec![0,1],filter_positions(&half_open.containing(10.)));
+    assert_eq!(Vec::<usize>::new(),filter_positions(&half_open.containing(20.)));
+    let closed = EachOrEveryIntervalIndex::new(&start,&end,4,EachOrEveryOverlap::Closed).unwrap();
+    assert_eq!(vec![0,1,3],filter_positions(&closed.containing(20.)));
+    let name = EachOrEvery::each(vec!["a","b","c","d"]);
+    assert_eq!(vec!["b"],values(&name.filter(&closed.overlapping(-5.,5.)),1));
+    assert!(EachOrEveryIntervalIndex::new(&start,&end,5,EachOrEveryOverlap::Closed).is_none());
+}
+
+#[test]
+fn test_bump() {
+    let start = EachOrEvery::each(vec![0.,5.,10.,12.,30.,f64::NAN]);
+    let end = EachOrEvery::each(vec![10.,15.,20.,25.,40.,50.]);
+    let (rows,fits) = EachOrEveryBumpBuilder::new(&start,&end,6).unwrap().bump().unwrap();
+    assert_eq!(vec![0,1,2,3,4],filter_positions(&fits));
+    assert_eq!(vec![0,1,0,2,0],values(&rows,5));
+    let (rows,_) = EachOrEveryBumpBuilder::new(&start,&end,6).unwrap().padding(1.).bump().unwrap();
+    assert_eq!(vec![0,1,2,0,0],values(&rows,5));
+    let (rows,fits) = EachOrEveryBumpBuilder::new(&start,&end,6).unwrap().max_rows(2).bump().unwrap();
+    assert_eq!(vec![0,1,2,4],filter_positions(&fits));
+    assert_eq!(vec![0,1,0,0],values(&rows,4));
+    let name = EachOrEvery::each(vec!["a","b","c","d","e","f"]);
+    assert_eq!(vec!["a","b","c","e"],values(&name.filter(&fits),4));
+    /* higher priority features claim the top rows */
+    let priority = EachOrEvery::each(vec![0.,0.,0.,1.,0.,0.]);
+    let (rows,fits) = EachOrEveryBumpBuilder::new(&start,&end,6).unwrap().priority(&priority).max_rows(2).bump().unwrap();
+    assert_eq!(vec![0,1,3,4],filter_positions(&fits));
+    assert_eq!(vec![0,1,0,0],values(&rows,4));
+    /* constant columns */
+    let (rows,fits) = EachOrEveryBumpBuilder::new(&EachOrEvery::every(0.),&EachOrEvery::every(1.),3).unwrap().bump().unwrap();
+    assert_eq!(3,fits.count());
+    assert_eq!(vec![0,1,2],values(&rows,3));
+    /* mismatched lengths */
+    assert!(EachOrEveryBumpBuilder::new(&start,&end,5).is_none());
+    assert!(EachOrEveryBumpBuilder::new(&start,&end,6).unwrap().priority(&EachOrEvery::repeat(1.,5)).bump().is_none());
+}
+
+#[test]
+fn test_bin() {
+    let position = EachOrEvery::each(vec![0.,10.,30.,35.,99.,100.,f64::NAN]);
+    let value = EachOrEvery::each(vec![1.,3.,2.,4.,5.,6.,7.]);
+    let bins = EachOrEveryBinBuilder::new(&position,7,0.,100.,4).unwrap();
+    assert_eq!(vec![0.,25.,50.,75.],bins.starts().iter().collect::<Vec<_>>());
+    assert_eq!(vec![1.,2.],values(&bins.bin(&value,EachOrEveryBinning::Min).unwrap(),4)[..2].to_vec());
+    assert_eq!(vec![3.,4.],values(&bins.bin(&value,EachOrEveryBinning::Max).unwrap(),4)[..2].to_vec());
+    let mean = bins.bin(&value,EachOrEveryBinning::Mean).unwrap();
+    assert_eq!(vec![2.,3.],values(&mean,4)[..2].to_vec());
+    assert!(mean.get(2).unwrap().is_nan());
+    assert_eq!(vec![2.,2.,0.,1.],values(&bins.coverage().unwrap(),4));
+    /* ranged data is weighted by overlap */
+    let start = EachOrEvery::each(vec![0.,20.,40.]);
+    let end = EachOrEvery::each(vec![30.,30.,200.]);
+    let value = EachOrEvery::each(vec![1.,4.,9.]);
+    let mut bins = EachOrEveryBinBuilder::new(&start,3,0.,100.,4).unwrap();
+    bins.end(&end);
+    assert_eq!(vec![1.5,5.75,9.,9.],values(&bins.bin(&value,EachOrEveryBinning::Mean).unwrap(),4));
+    assert_eq!(vec![4.,9.,9.,9.],values(&bins.bin(&value,EachOrEveryBinning::Max).unwrap(),4));
+    assert_eq!(vec![1.2,0.8,1.,1.],values(&bins.coverage().unwrap(),4));
+    /* constant values are never expanded */
+    assert_eq!(vec![5.,5.,5.,5.],values(&bins.bin(&EachOrEvery::every(5.),EachOrEveryBinning::Min).unwrap(),4));
+    /* mismatched lengths */
+    assert!(EachOrEveryBinBuilder::new(&start,4,0.,100.,4).is_none());
+    assert!(bins.bin(&EachOrEvery::each(vec![1.,2.]),EachOrEveryBinning::Mean).is_none());
+    bins.end(&EachOrEvery::repeat(50.,2));
+    assert!(bins.coverage().is_none());
+}
+
+#[test]
+fn test_interval_union() {
+    let start = EachOrEvery::each(vec![50.,0.,10.,30.,f64::NAN,40.]);
+    let end = EachOrEvery::each(vec![60.,15.,20.,40.,10.,45.]);
+    let union = EachOrEveryIntervalUnion::new(&start,&end,6,EachOrEveryOverlap::HalfOpen).unwrap();
+    assert_eq!(4,union.len());
+    assert_eq!(vec![0.,30.,40.,50.],values(&union.starts(),4));
+    assert_eq!(vec![20.,40.,45.,60.],values(&union.ends(),4));
+    assert_eq!(Some(&[1,2][..]),union.rows(0));
+    assert_eq!(vec![3],filter_positions(&union.filter(1).unwrap()));
+    let closed = EachOrEveryIntervalUnion::new(&start,&end,6,EachOrEveryOverlap::Closed).unwrap();
+    assert_eq!(vec![0.,30.,50.],values(&closed.starts(),3));
+    assert_eq!(vec![3,5],filter_positions(&closed.filter(1).unwrap()));
+    assert!(closed.filter(3).is_none());
+    /* attributes aggregate per block via the filters and gathers */
+    let score = EachOrEvery::each(vec![1.,2.,3.,4.,5.,6.]);
+    let total = EachOrEveryStats::new_filtered(&score,&closed.filter(0).unwrap()).unwrap().sum();
+    assert_eq!(5.,total);
+    assert_eq!(vec![4.,6.],values(&score.gather(closed.rows(1).unwrap()).unwrap(),2));
+    let every = EachOrEveryIntervalUnion::new(&EachOrEvery::every(0.),&EachOrEvery::every(10.),1000,EachOrEveryOverlap::HalfOpen).unwrap();
+    assert_eq!(1,every.len());
+    assert_eq!(1000,every.filter(0).unwrap().count());
+}
+
+#[test]
+fn test_sparse_dictionary_cache() {
+    /* a few rows of a large dictionary take the sparse cache path */
+    let names = EachOrEvery::each((0..1000).map(|x| format!("gene-{}",x)).collect::<Vec<_>>()).index(|x| x.clone());
+    let few = names.filter(&filter_of(1000,&[7,500,999]));
+    assert_eq!(1000,few.space());
+    let calls = Cell::new(0);
+    let sevens = few.make_filter(3,|x| { calls.set(calls.get()+1); x.ends_with('7') });
+    assert_eq!((vec![0],3),(filter_positions(&sevens),calls.get()));
+    assert_eq!(vec![6,8,8],few.map_positions(3,|x| x.len()).unwrap());
+    let upper = few.map_with_position(3,EachOrEveryPositional::Independent,|_,x| x.to_uppercase()).unwrap();
+    assert_eq!(vec!["GENE-7","GENE-500","GENE-999"],values(&upper,3));
+    let compacted = few.compact();
+    assert_eq!(3,compacted.space());
+    assert!(compacted == few);
+    let calls = Cell::new(0);
+    let paired = few.zip_with_position(&few.map(|x| x.len()),3,EachOrEveryPositional::Independent,|_,x,y| { calls.set(calls.get()+1); format!("{}/{}",x,y) }).unwrap();
+    assert_eq!(vec!["gene-7/6","gene-500/8","gene-999/8"],values(&paired,3));
+    assert_eq!(3,calls.get());
+    assert_eq!(3,paired.space());
+    let groups = EachOrEveryGroupBy::new(&few,3,|x| x.len()).unwrap();
+    assert_eq!(vec![6,8],values(&groups.keys(),2));
+    let merge = EachOrEveryTileMerge::new(&[(&few,3),(&few,3)],|x| x.clone()).unwrap();
+    assert_eq!(3,merge.count());
+}